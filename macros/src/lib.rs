@@ -0,0 +1,45 @@
+use proc_macro::TokenStream;
+use syn::{meta::ParseNestedMeta, parse::Parser, LitStr};
+
+/// Marks an `async fn` as an RPC handler for `client_gen` to generate a client method for.
+/// It leaves the function as it is, only checking its options:
+///
+/// ```ignore
+/// #[client_gen::rpc(name = "list_all", client = "patient", method = "patients.list")]
+/// pub async fn list_patients(ctx: Ctx, mm: ModelManager) -> Result<DataRpcResult<Vec<Patient>>> {
+///     ...
+/// }
+/// ```
+///
+/// - `name`: name of the method on the client object.
+/// - `client`: client object the method goes into instead of its file's.
+/// - `method`: JSON-RPC method the handler is registered under.
+/// - `skip`: leaves the handler out even when it is registered with the router.
+#[proc_macro_attribute]
+pub fn rpc(args: TokenStream, item: TokenStream) -> TokenStream {
+    let options = syn::meta::parser(|meta: ParseNestedMeta| {
+        if meta.path.is_ident("name")
+            || meta.path.is_ident("client")
+            || meta.path.is_ident("method")
+        {
+            meta.value()?.parse::<LitStr>()?;
+            Ok(())
+        } else if meta.path.is_ident("skip") {
+            Ok(())
+        } else {
+            Err(meta.error("unknown rpc option, expected `name`, `client`, `method` or `skip`"))
+        }
+    });
+
+    let checked = options
+        .parse(args)
+        .and_then(|_| syn::parse::<syn::ItemFn>(item.clone()).map(|_| ()));
+    match checked {
+        Ok(()) => item,
+        Err(e) => {
+            let mut tokens: TokenStream = e.to_compile_error().into();
+            tokens.extend(item);
+            tokens
+        }
+    }
+}
@@ -0,0 +1,224 @@
+mod json_schema;
+mod openrpc;
+mod type_mapper;
+mod typescript;
+
+pub use json_schema::{JsonSchema, JsonSchemaMapper};
+pub use openrpc::OpenRpc;
+pub use type_mapper::TsTypeMapper;
+pub use typescript::TypeScript;
+
+use crate::{
+    config::{Envelope, GeneratorConfig, ParamStructure},
+    ir::{Fields, Method, RpcModel, RustType, TypeDef, TypeDefKind},
+    Result,
+};
+
+/// Rust types serialized as JSON strings.
+pub(crate) const STRING_TYPES: &[&str] = &[
+    "String",
+    "str",
+    "char",
+    "Uuid",
+    "PathBuf",
+    "DateTime",
+    "NaiveDate",
+    "NaiveDateTime",
+    "NaiveTime",
+];
+
+/// Integers serialized as JSON numbers, wider ones too as serde_json does. Those that don't
+/// fit in a JS number can be mapped to something else under `[types]`.
+pub(crate) const INTEGER_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "i128", "u128", "isize", "usize",
+];
+
+pub(crate) const FLOAT_TYPES: &[&str] = &["f32", "f64"];
+
+pub(crate) const SEQUENCE_TYPES: &[&str] =
+    &["Vec", "VecDeque", "LinkedList", "HashSet", "BTreeSet"];
+
+pub(crate) const MAP_TYPES: &[&str] = &["HashMap", "BTreeMap", "IndexMap"];
+
+/// Types serialized exactly like the type they wrap.
+pub(crate) const TRANSPARENT_TYPES: &[&str] = &["Box", "Rc", "Arc", "Cow"];
+
+/// Turns an [`RpcModel`] into the contents of one output file.
+pub trait Backend {
+    /// Contents of the output file describing `model`, as configured by `config`.
+    fn render(&self, model: &RpcModel, config: &GeneratorConfig) -> Result<String>;
+}
+
+/// How the params of `method` are sent: as `configured`, except by position when some of
+/// those sent under their name destructure a pattern instead of binding a name.
+pub(crate) fn param_structure(
+    method: &Method,
+    model: &RpcModel,
+    configured: ParamStructure,
+) -> ParamStructure {
+    if configured == ParamStructure::ByPosition || params_struct(method, model).is_some() {
+        return configured;
+    }
+
+    let unnamed = method.params.iter().find(|param| {
+        let mut chars = param.name.chars();
+        !chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            || !chars.all(|c| c.is_alphanumeric() || c == '_')
+    });
+    match unnamed {
+        Some(param) => {
+            eprintln!(
+                "WARNING: Handler: {}, sending its params by position as `{}` has no name",
+                method.name, param.name
+            );
+            ParamStructure::ByPosition
+        }
+        None => configured,
+    }
+}
+
+/// Definition of the only param of `method` when it is sent by name as the params object
+/// itself: a struct with named fields, none of them flattened. JSON-RPC params have to be
+/// an object or an array, so any other param is sent under its name, like several are.
+pub(crate) fn params_struct<'a>(method: &Method, model: &'a RpcModel) -> Option<&'a TypeDef> {
+    let [param] = method.params.as_slice() else {
+        return None;
+    };
+    let def = model.types.get(param.ty.name()?)?;
+
+    match &def.kind {
+        TypeDefKind::Struct(Fields::Named(fields)) if fields.iter().all(|f| !f.flatten) => {
+            Some(def)
+        }
+        _ => None,
+    }
+}
+
+/// The payload of a handler's result type, inside its `Result` and any of the `envelopes`
+/// wrapping it. Comes with the fields of those envelopes the payload is serialized under,
+/// outermost first, e.g. `(["data"], T)` for `Result<DataRpcResult<T>>`.
+pub(crate) fn result_payload<'a>(
+    result: &'a RustType,
+    envelopes: &'a [Envelope],
+) -> (Vec<&'a str>, &'a RustType) {
+    let mut payload = match result.args() {
+        [ok, ..] if result.name() == Some("Result") => ok,
+        _ => result,
+    };
+    let mut fields = vec![];
+
+    while let Some(envelope) = envelopes.iter().find(|envelope| {
+        payload.name() == Some(envelope.name())
+            || payload.path().as_deref() == Some(envelope.name())
+    }) {
+        let Some(inner) = payload.args().first() else {
+            break;
+        };
+        fields.extend(envelope.field());
+        payload = inner;
+    }
+
+    (fields, payload)
+}
+
+/// `E` of a `Result<T, E>` return type, `None` for the crate's `Result<T>` alias.
+pub(crate) fn result_error(result: &RustType) -> Option<&RustType> {
+    match result.args() {
+        [_, error] if result.name() == Some("Result") => Some(error),
+        _ => None,
+    }
+}
+
+/// Models for the tests of the backends, built from Rust source.
+#[cfg(test)]
+pub(crate) mod test_model {
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    use crate::{
+        ir::{Method, Param, RpcModel, Service, SourceLocation, TypeDef},
+        parser::{self, parse_type},
+    };
+
+    /// Method `name` taking `params` as `(name, type)` pairs and returning `result`.
+    pub fn method(name: &str, params: &[(&str, &str)], result: &str) -> Method {
+        Method {
+            name: name.to_owned(),
+            rpc_name: name.to_owned(),
+            params: params
+                .iter()
+                .map(|(name, ty)| Param {
+                    name: (*name).to_owned(),
+                    ty: parse_type(ty).unwrap(),
+                })
+                .collect(),
+            result: parse_type(result).unwrap(),
+            docs: vec![],
+            location: SourceLocation {
+                file: PathBuf::from("task_rpc.rs"),
+                line: 1,
+            },
+        }
+    }
+
+    pub fn model(client: &str, methods: Vec<Method>) -> RpcModel {
+        RpcModel {
+            services: vec![Service {
+                name: client.to_owned(),
+                methods,
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// The type definitions of `source`, by name.
+    pub fn types(source: &str) -> BTreeMap<String, TypeDef> {
+        let path = std::path::Path::new("model.rs");
+        let file = parser::parse_file(path, source).unwrap();
+        parser::type_defs(&file, path)
+            .into_iter()
+            .map(|def| (def.name.clone(), def))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{test_model::*, *};
+
+    #[test]
+    fn backends_agree_on_the_params_sent() {
+        let model = RpcModel {
+            types: types("pub struct ParamsIded { pub id: i64 }"),
+            ..model(
+                "patient",
+                vec![
+                    method("get_patient", &[("id", "i64")], "Result<Patient>"),
+                    method(
+                        "find_patient",
+                        &[("params", "ParamsIded")],
+                        "Result<Patient>",
+                    ),
+                ],
+            )
+        };
+        let config = GeneratorConfig::new("/app");
+
+        let client = TypeScript.render(&model, &config).unwrap();
+        assert!(client.contains("async get_patient(id: string) {"));
+        assert!(client.contains("params: { id },"));
+        assert!(client.contains("async find_patient(params: ParamsIded) {"));
+        assert!(client.contains("params: params,"));
+
+        let document: Value =
+            serde_json::from_str(&OpenRpc.render(&model, &config).unwrap()).unwrap();
+        for method in document["methods"].as_array().unwrap() {
+            assert_eq!(method["paramStructure"], "by-name");
+            assert_eq!(
+                method["params"],
+                json!([{ "name": "id", "required": true, "schema": { "type": "integer" } }])
+            );
+        }
+    }
+}
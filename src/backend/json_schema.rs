@@ -0,0 +1,533 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    backend::{
+        param_structure, params_struct, result_payload, type_mapper::custom_mapping, Backend,
+        FLOAT_TYPES, INTEGER_TYPES, MAP_TYPES, SEQUENCE_TYPES, STRING_TYPES, TRANSPARENT_TYPES,
+    },
+    config::{Envelope, GeneratorConfig, ParamStructure, TypeMapping},
+    ir::{EnumTagging, Fields, Method, RpcModel, RustType, TypeDef, TypeDefKind, Variant},
+    Error, Result,
+};
+
+/// The JSON Schema definitions of every type the model's methods take or return, and of the
+/// params and result of each method, as `<method>.params` and `<method>.result`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSchema;
+
+impl Backend for JsonSchema {
+    fn render(&self, model: &RpcModel, config: &GeneratorConfig) -> Result<String> {
+        let mut mapper = JsonSchemaMapper::new(config.type_mappings.clone(), "#/$defs/");
+        let mut methods = Map::new();
+        for (_, method) in model.methods() {
+            let structure = param_structure(method, model, config.param_structure);
+            if let Some(params) = params_schema(method, model, structure, &mut mapper) {
+                methods.insert(format!("{}.params", method.rpc_name), params);
+            }
+            let result = result_schema(&method.result, &config.envelopes, &mut mapper);
+            methods.insert(format!("{}.result", method.rpc_name), result);
+        }
+
+        let mut definitions = mapper.definitions(&model.types);
+        definitions.extend(methods);
+        let document = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": format!("{} RPC types", config.name),
+            "$defs": definitions,
+        });
+
+        serde_json::to_string_pretty(&document).map_err(|e| Error::Serialize(e.to_string()))
+    }
+}
+
+/// Schema of the JSON-RPC `params` of `method` sent with `structure`, `None` when it takes
+/// none.
+pub(crate) fn params_schema(
+    method: &Method,
+    model: &RpcModel,
+    structure: ParamStructure,
+    mapper: &mut JsonSchemaMapper,
+) -> Option<Value> {
+    if method.params.is_empty() {
+        return None;
+    }
+    if let (Some(_), ParamStructure::ByName) = (params_struct(method, model), structure) {
+        return Some(mapper.schema(&method.params[0].ty));
+    }
+
+    let schemas: Vec<Value> = method
+        .params
+        .iter()
+        .map(|param| mapper.schema(&param.ty))
+        .collect();
+    let names: Vec<String> = method.params.iter().map(|p| p.name.clone()).collect();
+
+    Some(match structure {
+        ParamStructure::ByName => json!({
+            "type": "object",
+            "properties": names.iter().cloned().zip(schemas).collect::<Map<String, Value>>(),
+            "required": names,
+        }),
+        ParamStructure::ByPosition => json!({
+            "type": "array",
+            "prefixItems": schemas,
+            "minItems": names.len(),
+            "maxItems": names.len(),
+        }),
+    })
+}
+
+/// Schema of the JSON-RPC `result` of a handler returning `result`: its payload inside the
+/// fields of the `envelopes` wrapping it.
+pub(crate) fn result_schema(
+    result: &RustType,
+    envelopes: &[Envelope],
+    mapper: &mut JsonSchemaMapper,
+) -> Value {
+    let (fields, payload) = result_payload(result, envelopes);
+
+    fields
+        .iter()
+        .rev()
+        .fold(mapper.schema(payload), |schema, field| {
+            json!({
+                "type": "object",
+                "properties": { *field: schema },
+                "required": [field],
+            })
+        })
+}
+
+/// Translates Rust types into JSON Schema. Named types become `$ref`s and are remembered, so
+/// their definitions can be emitted with [`JsonSchemaMapper::definitions`].
+#[derive(Debug, Clone, Default)]
+pub struct JsonSchemaMapper {
+    custom: BTreeMap<String, TypeMapping>,
+    ref_prefix: String,
+    /// Schema key of every referenced type, with its name and generic arguments.
+    referenced: BTreeMap<String, (String, Vec<RustType>)>,
+}
+
+impl JsonSchemaMapper {
+    /// Custom TypeScript mappings to a primitive (`string`, `number`, `boolean`) are honoured,
+    /// anything else they map to is left as an unconstrained schema. `ref_prefix` is where
+    /// the definitions will live, e.g. `#/$defs/`.
+    pub fn new(custom: BTreeMap<String, TypeMapping>, ref_prefix: &str) -> Self {
+        JsonSchemaMapper {
+            custom,
+            ref_prefix: ref_prefix.to_owned(),
+            referenced: BTreeMap::new(),
+        }
+    }
+
+    /// Schemas of every type referenced so far, keyed by schema key. Types without a
+    /// definition in `types` get a schema accepting anything, and a warning.
+    pub fn definitions(&mut self, types: &BTreeMap<String, TypeDef>) -> Map<String, Value> {
+        let mut definitions = Map::new();
+
+        loop {
+            let pending: Vec<(String, (String, Vec<RustType>))> = self
+                .referenced
+                .iter()
+                .filter(|(key, _)| !definitions.contains_key(*key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            if pending.is_empty() {
+                return definitions;
+            }
+
+            for (key, (name, args)) in pending {
+                let schema = match types.get(&name) {
+                    Some(def) => self.definition(def, &args),
+                    None => {
+                        eprintln!("WARNING: Type: {name}, no definition found in the scanned tree, its schema accepts any value");
+                        json!({ "title": name })
+                    }
+                };
+                definitions.insert(key, schema);
+            }
+        }
+    }
+
+    /// Schema of `def` instantiated with `args` for its generic parameters.
+    pub fn definition(&mut self, def: &TypeDef, args: &[RustType]) -> Value {
+        let generics: BTreeMap<String, RustType> = def
+            .generics
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+
+        let mut schema = match &def.kind {
+            TypeDefKind::Struct(fields) => self.fields_schema(fields, &generics),
+            TypeDefKind::Enum { variants, tagging } => {
+                self.enum_schema(variants, tagging, &generics)
+            }
+        };
+        schema["title"] = Value::String(def.name.clone());
+        if !def.docs.is_empty() {
+            schema["description"] = Value::String(def.docs.join("\n"));
+        }
+        schema
+    }
+
+    fn fields_schema(&mut self, fields: &Fields, generics: &BTreeMap<String, RustType>) -> Value {
+        match fields {
+            Fields::Named(fields) => {
+                let mut properties = Map::new();
+                let mut required = vec![];
+                let mut flattened = vec![];
+                for field in fields {
+                    let mut schema = self.schema(&field.ty.substitute(generics));
+                    if field.flatten {
+                        flattened.push(schema);
+                        continue;
+                    }
+                    if !field.docs.is_empty() {
+                        schema["description"] = Value::String(field.docs.join("\n"));
+                    }
+                    if field.required {
+                        required.push(field.name.clone());
+                    }
+                    properties.insert(field.name.clone(), schema);
+                }
+                let object = json!({
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                });
+                if flattened.is_empty() {
+                    object
+                } else {
+                    flattened.insert(0, object);
+                    json!({ "allOf": flattened })
+                }
+            }
+            Fields::Newtype(ty) => self.schema(&ty.substitute(generics)),
+            Fields::Tuple(types) => {
+                let types: Vec<RustType> = types.iter().map(|ty| ty.substitute(generics)).collect();
+                self.schema(&RustType::Tuple(types))
+            }
+            Fields::Unit => json!({ "type": "null" }),
+        }
+    }
+
+    fn enum_schema(
+        &mut self,
+        variants: &[Variant],
+        tagging: &EnumTagging,
+        generics: &BTreeMap<String, RustType>,
+    ) -> Value {
+        let all_unit = variants.iter().all(|v| v.fields == Fields::Unit);
+        if all_unit && matches!(tagging, EnumTagging::External) {
+            let names: Vec<&str> = variants.iter().map(|v| v.name.as_str()).collect();
+            return json!({ "type": "string", "enum": names });
+        }
+
+        let one_of: Vec<Value> = variants
+            .iter()
+            .map(|variant| {
+                let name = &variant.name;
+                match (tagging, &variant.fields) {
+                    (EnumTagging::External, Fields::Unit) => json!({ "const": name }),
+                    (EnumTagging::External, fields) => json!({
+                        "type": "object",
+                        "properties": { name.as_str(): self.fields_schema(fields, generics) },
+                        "required": [name],
+                        "additionalProperties": false,
+                    }),
+                    (EnumTagging::Internal { tag }, Fields::Unit) => json!({
+                        "type": "object",
+                        "properties": { tag.as_str(): { "const": name } },
+                        "required": [tag],
+                    }),
+                    (EnumTagging::Internal { tag }, fields) => json!({
+                        "allOf": [
+                            {
+                                "type": "object",
+                                "properties": { tag.as_str(): { "const": name } },
+                                "required": [tag],
+                            },
+                            self.fields_schema(fields, generics),
+                        ],
+                    }),
+                    (EnumTagging::Adjacent { tag, .. }, Fields::Unit) => json!({
+                        "type": "object",
+                        "properties": { tag.as_str(): { "const": name } },
+                        "required": [tag],
+                    }),
+                    (EnumTagging::Adjacent { tag, content }, fields) => json!({
+                        "type": "object",
+                        "properties": {
+                            tag.as_str(): { "const": name },
+                            content.as_str(): self.fields_schema(fields, generics),
+                        },
+                        "required": [tag, content],
+                    }),
+                    (EnumTagging::Untagged, fields) => self.fields_schema(fields, generics),
+                }
+            })
+            .collect();
+
+        json!({ "oneOf": one_of })
+    }
+
+    /// Schema of the JSON `ty` serializes into, remembering the named types it references.
+    pub fn schema(&mut self, ty: &RustType) -> Value {
+        match ty {
+            RustType::Path { segments, args } => {
+                if let Some(mapping) = custom_mapping(&self.custom, segments) {
+                    return match mapping.ts() {
+                        "string" => json!({ "type": "string" }),
+                        "number" => json!({ "type": "number" }),
+                        "boolean" => json!({ "type": "boolean" }),
+                        _ => json!({}),
+                    };
+                }
+                let name = segments.last().map(String::as_str).unwrap_or_default();
+                self.path_schema(name, args)
+            }
+            RustType::Tuple(types) if types.is_empty() => json!({ "type": "null" }),
+            RustType::Tuple(types) => {
+                let items: Vec<Value> = types.iter().map(|ty| self.schema(ty)).collect();
+                json!({
+                    "type": "array",
+                    "prefixItems": items,
+                    "minItems": types.len(),
+                    "maxItems": types.len(),
+                })
+            }
+            RustType::Array(inner) => json!({ "type": "array", "items": self.schema(inner) }),
+            RustType::Reference(inner) => self.schema(inner),
+            RustType::Other(_) => json!({}),
+        }
+    }
+
+    fn path_schema(&mut self, name: &str, args: &[RustType]) -> Value {
+        match (name, args) {
+            ("Option", [inner]) => json!({ "anyOf": [self.schema(inner), { "type": "null" }] }),
+            (name, [inner]) if SEQUENCE_TYPES.contains(&name) => {
+                json!({ "type": "array", "items": self.schema(inner) })
+            }
+            (name, [_, value]) if MAP_TYPES.contains(&name) => {
+                json!({ "type": "object", "additionalProperties": self.schema(value) })
+            }
+            (name, [inner]) if TRANSPARENT_TYPES.contains(&name) => self.schema(inner),
+            ("bool", []) => json!({ "type": "boolean" }),
+            (name, []) if FLOAT_TYPES.contains(&name) => json!({ "type": "number" }),
+            ("Value", []) => json!({}),
+            ("Uuid", []) => json!({ "type": "string", "format": "uuid" }),
+            ("NaiveDate", []) => json!({ "type": "string", "format": "date" }),
+            ("DateTime" | "NaiveDateTime", _) => json!({ "type": "string", "format": "date-time" }),
+            ("NaiveTime", []) => json!({ "type": "string", "format": "time" }),
+            (name, []) if STRING_TYPES.contains(&name) => json!({ "type": "string" }),
+            (name, []) if INTEGER_TYPES.contains(&name) => json!({ "type": "integer" }),
+            (name, args) => {
+                let key = schema_key(name, args);
+                self.referenced
+                    .insert(key.clone(), (name.to_owned(), args.to_vec()));
+                json!({ "$ref": format!("{}{key}", self.ref_prefix) })
+            }
+        }
+    }
+}
+
+/// Name of the schema of a named type, generic arguments joined with `_` since component
+/// names can't contain `<>`, e.g. `ParamsList_PatientFilter`.
+pub fn schema_key(name: &str, args: &[RustType]) -> String {
+    let mut key = name.to_owned();
+    for arg in args {
+        key.push('_');
+        key.push_str(&arg_key(arg));
+    }
+    key
+}
+
+fn arg_key(ty: &RustType) -> String {
+    match ty {
+        RustType::Path { segments, args } => schema_key(
+            segments.last().map(String::as_str).unwrap_or_default(),
+            args,
+        ),
+        RustType::Tuple(types) if types.is_empty() => "Unit".to_owned(),
+        RustType::Tuple(types) => types.iter().map(arg_key).collect::<Vec<String>>().join("_"),
+        RustType::Array(inner) => format!("{}Array", arg_key(inner)),
+        RustType::Reference(inner) => arg_key(inner),
+        RustType::Other(_) => "Unknown".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::test_model::{method, model, types},
+        parser::parse_type,
+    };
+
+    fn schema(ty: &str) -> Value {
+        JsonSchemaMapper::default().schema(&parse_type(ty).unwrap())
+    }
+
+    #[test]
+    fn integers_stay_numbers() {
+        assert_eq!(schema("i64"), json!({ "type": "integer" }));
+        assert_eq!(schema("u64"), json!({ "type": "integer" }));
+        assert_eq!(
+            schema("Option<i64>"),
+            json!({ "anyOf": [{ "type": "integer" }, { "type": "null" }] })
+        );
+    }
+
+    fn defs_ref(name: &str) -> Value {
+        json!({ "$ref": format!("#/$defs/{name}") })
+    }
+
+    #[test]
+    fn params_and_result_of_each_method() {
+        let mut create = method(
+            "create_task",
+            &[("params", "ParamsForCreate<TaskForCreate>")],
+            "Result<DataRpcResult<Task>>",
+        );
+        create.rpc_name = "tasks.create".to_owned();
+        let methods = vec![
+            create,
+            method("list_tasks", &[], "Result<DataRpcResult<Vec<Task>>>"),
+            method(
+                "rename",
+                &[("id", "i64"), ("title", "String")],
+                "Result<()>",
+            ),
+        ];
+        let model = RpcModel {
+            types: types(
+                r#"
+                pub struct ParamsForCreate<D> { pub data: D }
+                pub struct TaskForCreate { pub title: String }
+                pub struct Task { pub id: i64, pub title: String }
+                "#,
+            ),
+            ..model("task", methods)
+        };
+        let document = JsonSchema
+            .render(&model, &GeneratorConfig::new("/app"))
+            .unwrap();
+        let document: Value = serde_json::from_str(&document).unwrap();
+        let defs = document["$defs"].as_object().unwrap();
+
+        assert_eq!(
+            defs.keys().collect::<Vec<_>>(),
+            [
+                "ParamsForCreate_TaskForCreate",
+                "Task",
+                "TaskForCreate",
+                "list_tasks.result",
+                "rename.params",
+                "rename.result",
+                "tasks.create.params",
+                "tasks.create.result",
+            ]
+        );
+        assert_eq!(
+            defs["tasks.create.params"],
+            defs_ref("ParamsForCreate_TaskForCreate")
+        );
+        assert_eq!(
+            defs["ParamsForCreate_TaskForCreate"]["properties"]["data"],
+            defs_ref("TaskForCreate")
+        );
+        assert_eq!(
+            defs["list_tasks.result"],
+            json!({
+                "type": "object",
+                "properties": { "data": { "type": "array", "items": defs_ref("Task") } },
+                "required": ["data"],
+            })
+        );
+        assert_eq!(
+            defs["rename.params"],
+            json!({
+                "type": "object",
+                "properties": { "id": { "type": "integer" }, "title": { "type": "string" } },
+                "required": ["id", "title"],
+            })
+        );
+        assert_eq!(defs["rename.result"], json!({ "type": "null" }));
+    }
+
+    #[test]
+    fn definitions_follow_serde_attributes() {
+        let types = types(
+            r#"
+            #[serde(tag = "type", rename_all = "snake_case")]
+            pub enum TaskEvent { Created { id: i64 }, Closed }
+
+            pub enum Status { Open, Done }
+
+            pub struct Page {
+                #[serde(flatten)]
+                pub options: ListOptions,
+                pub total: u32,
+            }
+
+            pub struct ListOptions { pub limit: Option<u32> }
+            "#,
+        );
+        let mut mapper = JsonSchemaMapper::new(BTreeMap::new(), "#/$defs/");
+        for name in ["TaskEvent", "Status", "Page", "Missing"] {
+            mapper.schema(&parse_type(name).unwrap());
+        }
+        let defs = mapper.definitions(&types);
+
+        assert_eq!(
+            defs["TaskEvent"],
+            json!({
+                "title": "TaskEvent",
+                "oneOf": [
+                    {
+                        "allOf": [
+                            {
+                                "type": "object",
+                                "properties": { "type": { "const": "created" } },
+                                "required": ["type"],
+                            },
+                            {
+                                "type": "object",
+                                "properties": { "id": { "type": "integer" } },
+                                "required": ["id"],
+                            },
+                        ],
+                    },
+                    {
+                        "type": "object",
+                        "properties": { "type": { "const": "closed" } },
+                        "required": ["type"],
+                    },
+                ],
+            })
+        );
+        assert_eq!(
+            defs["Status"],
+            json!({ "title": "Status", "type": "string", "enum": ["Open", "Done"] })
+        );
+        assert_eq!(
+            defs["Page"],
+            json!({
+                "title": "Page",
+                "allOf": [
+                    {
+                        "type": "object",
+                        "properties": { "total": { "type": "integer" } },
+                        "required": ["total"],
+                    },
+                    defs_ref("ListOptions"),
+                ],
+            })
+        );
+        assert_eq!(defs["ListOptions"]["required"], json!([]));
+        assert_eq!(defs["Missing"], json!({ "title": "Missing" }));
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+use crate::{
+    backend::{
+        json_schema::result_schema, param_structure, params_struct, result_error, Backend,
+        JsonSchemaMapper,
+    },
+    config::{GeneratorConfig, ParamStructure},
+    ir::{Fields, Method, Param, RpcModel, RustType, TypeDef, TypeDefKind},
+    Error, Result,
+};
+
+const OPENRPC_VERSION: &str = "1.3.2";
+
+/// An [OpenRPC](https://spec.open-rpc.org) document describing every method of the model.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenRpc;
+
+impl Backend for OpenRpc {
+    fn render(&self, model: &RpcModel, config: &GeneratorConfig) -> Result<String> {
+        let mut mapper =
+            JsonSchemaMapper::new(config.type_mappings.clone(), "#/components/schemas/");
+
+        let methods: Vec<Value> = model
+            .services
+            .iter()
+            .flat_map(|service| {
+                service
+                    .methods
+                    .iter()
+                    .map(|method| (service.name.as_str(), method))
+            })
+            .map(|(service, method)| method_object(service, method, model, config, &mut mapper))
+            .collect();
+
+        let schemas = mapper.definitions(&model.types);
+
+        let document = json!({
+            "openrpc": OPENRPC_VERSION,
+            "info": {
+                "title": format!("{} RPC API", config.name),
+                "version": "1.0.0",
+            },
+            "servers": [{ "name": config.name, "url": config.rpc_endpoint }],
+            "methods": methods,
+            "components": { "schemas": schemas },
+        });
+
+        serde_json::to_string_pretty(&document).map_err(|e| Error::Serialize(e.to_string()))
+    }
+}
+
+fn method_object(
+    service: &str,
+    method: &Method,
+    model: &RpcModel,
+    config: &GeneratorConfig,
+    mapper: &mut JsonSchemaMapper,
+) -> Value {
+    let structure = param_structure(method, model, config.param_structure);
+    let params: Vec<Value> = match (params_struct(method, model), structure) {
+        (Some(def), ParamStructure::ByName) => struct_fields(def, &method.params[0], mapper),
+        _ => method
+            .params
+            .iter()
+            .map(|param| descriptor(&param.name, mapper.schema(&param.ty), true))
+            .collect(),
+    };
+    let param_structure = match structure {
+        ParamStructure::ByName => "by-name",
+        ParamStructure::ByPosition => "by-position",
+    };
+
+    let result = result_schema(&method.result, &config.envelopes, mapper);
+
+    let mut object = json!({
+        "name": method.rpc_name,
+        "tags": [{ "name": service }],
+        "paramStructure": param_structure,
+        "params": params,
+        "result": { "name": "result", "schema": result },
+    });
+    if !method.docs.is_empty() {
+        object["description"] = Value::String(method.docs.join("\n"));
+    }
+    // OpenRPC error objects can't carry a schema, so the `data` of the errors a handler
+    // returns as `Result<T, E>` is described by an extension, as the client types it.
+    if let Some(error) = result_error(&method.result) {
+        object["x-error"] = json!({
+            "name": "error",
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "detail": mapper.schema(error),
+                    "req_uuid": { "type": "string" },
+                },
+                "required": ["detail", "req_uuid"],
+            },
+        });
+    }
+    object
+}
+
+/// Content descriptors of the fields of `def`, the struct `param` sends as the params
+/// object.
+fn struct_fields(def: &TypeDef, param: &Param, mapper: &mut JsonSchemaMapper) -> Vec<Value> {
+    let TypeDefKind::Struct(Fields::Named(fields)) = &def.kind else {
+        return vec![];
+    };
+    let generics: BTreeMap<String, RustType> = def
+        .generics
+        .iter()
+        .cloned()
+        .zip(param.ty.args().iter().cloned())
+        .collect();
+
+    fields
+        .iter()
+        .map(|field| {
+            descriptor(
+                &field.name,
+                mapper.schema(&field.ty.substitute(&generics)),
+                field.required,
+            )
+        })
+        .collect()
+}
+
+fn descriptor(name: &str, schema: Value, required: bool) -> Value {
+    json!({ "name": name, "required": required, "schema": schema })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_model::{method, model, types};
+
+    const TYPES: &str = r#"
+        pub struct ParamsIded { pub id: i64 }
+        pub struct ParamsForCreate<D> { pub data: D }
+        pub struct Task { pub id: i64, pub title: String }
+        pub struct TaskForCreate { pub title: String }
+        pub enum TaskError { NotFound, Forbidden }
+    "#;
+
+    fn render(methods: Vec<Method>) -> Value {
+        let model = RpcModel {
+            types: types(TYPES),
+            ..model("task", methods)
+        };
+        let document = OpenRpc
+            .render(&model, &GeneratorConfig::new("/app"))
+            .unwrap();
+        serde_json::from_str(&document).unwrap()
+    }
+
+    fn schema_ref(name: &str) -> Value {
+        json!({ "$ref": format!("#/components/schemas/{name}") })
+    }
+
+    #[test]
+    fn methods_by_rpc_name_and_param_structure() {
+        let mut create = method(
+            "create_task",
+            &[("params", "ParamsForCreate<TaskForCreate>")],
+            "Result<DataRpcResult<Task>>",
+        );
+        create.rpc_name = "tasks.create".to_owned();
+        let document = render(vec![
+            create,
+            method(
+                "rename",
+                &[("id", "i64"), ("title", "String")],
+                "Result<Task>",
+            ),
+            method(
+                "swap",
+                &[("(a, b)", "(i64, i64)"), ("c", "u8")],
+                "Result<()>",
+            ),
+        ]);
+
+        let methods = document["methods"].as_array().unwrap();
+        let names: Vec<&str> = methods
+            .iter()
+            .map(|m| m["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["tasks.create", "rename", "swap"]);
+        assert_eq!(methods[0]["tags"], json!([{ "name": "task" }]));
+
+        assert_eq!(methods[0]["paramStructure"], "by-name");
+        assert_eq!(
+            methods[0]["params"],
+            json!([{ "name": "data", "required": true, "schema": schema_ref("TaskForCreate") }])
+        );
+        assert_eq!(methods[1]["paramStructure"], "by-name");
+        assert_eq!(
+            methods[1]["params"],
+            json!([
+                { "name": "id", "required": true, "schema": { "type": "integer" } },
+                { "name": "title", "required": true, "schema": { "type": "string" } },
+            ])
+        );
+        assert_eq!(methods[2]["paramStructure"], "by-position");
+        assert_eq!(methods[2]["params"][1]["name"], "c");
+    }
+
+    #[test]
+    fn results_and_errors_inside_their_envelopes() {
+        let document = render(vec![
+            method("get", &[], "Result<DataRpcResult<Task>>"),
+            method("fetch", &[], "Result<Json<Vec<Task>>, TaskError>"),
+        ]);
+        let methods = document["methods"].as_array().unwrap();
+
+        assert_eq!(
+            methods[0]["result"]["schema"],
+            json!({
+                "type": "object",
+                "properties": { "data": schema_ref("Task") },
+                "required": ["data"],
+            })
+        );
+        assert!(methods[0].get("x-error").is_none());
+
+        assert_eq!(
+            methods[1]["result"]["schema"],
+            json!({ "type": "array", "items": schema_ref("Task") })
+        );
+        assert_eq!(
+            methods[1]["x-error"]["schema"]["properties"]["detail"],
+            schema_ref("TaskError")
+        );
+    }
+
+    #[test]
+    fn refs_resolve_into_components() {
+        let document = render(vec![
+            method(
+                "create",
+                &[("params", "ParamsForCreate<TaskForCreate>")],
+                "Result<Task, TaskError>",
+            ),
+            method(
+                "get",
+                &[("params", "ParamsIded")],
+                "Result<DataRpcResult<Task>>",
+            ),
+        ]);
+
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        assert_eq!(
+            schemas.keys().collect::<Vec<_>>(),
+            ["Task", "TaskError", "TaskForCreate"]
+        );
+        assert_eq!(
+            schemas["Task"]["properties"]["title"],
+            json!({ "type": "string" })
+        );
+        assert_eq!(
+            schemas["TaskError"]["enum"],
+            json!(["NotFound", "Forbidden"])
+        );
+    }
+}
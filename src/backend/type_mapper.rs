@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    backend::{
+        FLOAT_TYPES, INTEGER_TYPES, MAP_TYPES, SEQUENCE_TYPES, STRING_TYPES, TRANSPARENT_TYPES,
+    },
+    config::TypeMapping,
+    ir::RustType,
+    util::type_path_matches,
+};
+
+/// Translates Rust types into TypeScript, consulting user-registered mappings first.
+#[derive(Debug, Clone, Default)]
+pub struct TsTypeMapper {
+    custom: BTreeMap<String, TypeMapping>,
+}
+
+impl TsTypeMapper {
+    /// `custom` maps a Rust type path to the TypeScript type to emit. A full path like
+    /// `time::OffsetDateTime` matches the types written with a path ending in it, a bare
+    /// name like `OffsetDateTime` matches the types of that name however they're written.
+    pub fn new(custom: BTreeMap<String, TypeMapping>) -> Self {
+        TsTypeMapper { custom }
+    }
+
+    /// TypeScript type of the JSON `ty` serializes into.
+    pub fn map(&self, ty: &RustType) -> String {
+        match ty {
+            RustType::Path { segments, args } => {
+                if let Some(mapping) = custom_mapping(&self.custom, segments) {
+                    return mapping.ts().to_owned();
+                }
+                let name = segments.last().map(String::as_str).unwrap_or_default();
+                self.map_path(name, args)
+            }
+            RustType::Tuple(types) if types.is_empty() => "null".to_owned(),
+            RustType::Tuple(types) => format!("[{}]", self.map_all(types)),
+            RustType::Array(inner) => self.array_of(inner),
+            RustType::Reference(inner) => self.map(inner),
+            RustType::Other(_) => "unknown".to_owned(),
+        }
+    }
+
+    /// Whether `ty` has a user-registered mapping.
+    pub fn is_custom(&self, ty: &RustType) -> bool {
+        match ty {
+            RustType::Path { segments, .. } => custom_mapping(&self.custom, segments).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Imports needed by the custom mappings used in `types`, as names per module.
+    pub fn imports<'a>(
+        &self,
+        types: impl IntoIterator<Item = &'a RustType>,
+    ) -> BTreeMap<String, BTreeSet<String>> {
+        let mut imports = BTreeMap::new();
+        for ty in types {
+            self.collect_imports(ty, &mut imports);
+        }
+        imports
+    }
+
+    fn collect_imports(&self, ty: &RustType, imports: &mut BTreeMap<String, BTreeSet<String>>) {
+        match ty {
+            RustType::Path { segments, args } => match custom_mapping(&self.custom, segments) {
+                Some(mapping) => {
+                    if let Some(import) = mapping.import() {
+                        let name = import.name.as_deref().unwrap_or(mapping.ts());
+                        imports
+                            .entry(import.from.clone())
+                            .or_default()
+                            .insert(name.to_owned());
+                    }
+                }
+                None => args
+                    .iter()
+                    .for_each(|arg| self.collect_imports(arg, imports)),
+            },
+            RustType::Tuple(types) => types
+                .iter()
+                .for_each(|ty| self.collect_imports(ty, imports)),
+            RustType::Array(inner) | RustType::Reference(inner) => {
+                self.collect_imports(inner, imports)
+            }
+            RustType::Other(_) => {}
+        }
+    }
+
+    fn map_path(&self, name: &str, args: &[RustType]) -> String {
+        match (name, args) {
+            ("Option", [inner]) => format!("{} | null", self.map(inner)),
+            (name, [inner]) if SEQUENCE_TYPES.contains(&name) => self.array_of(inner),
+            (name, [key, value]) if MAP_TYPES.contains(&name) => {
+                format!("Record<{}, {}>", self.map(key), self.map(value))
+            }
+            (name, [inner]) if TRANSPARENT_TYPES.contains(&name) => self.map(inner),
+            ("bool", []) => "boolean".to_owned(),
+            ("Value", []) => "unknown".to_owned(),
+            (name, _) if STRING_TYPES.contains(&name) => "string".to_owned(),
+            // By convention the client types the backend's `i64`s, mostly ids, as strings.
+            ("i64", []) => "string".to_owned(),
+            (name, []) if INTEGER_TYPES.contains(&name) || FLOAT_TYPES.contains(&name) => {
+                "number".to_owned()
+            }
+            (name, []) => name.to_owned(),
+            (name, args) => format!("{name}<{}>", self.map_all(args)),
+        }
+    }
+
+    fn array_of(&self, inner: &RustType) -> String {
+        let inner = self.map(inner);
+        if inner.contains(' ') {
+            format!("({inner})[]")
+        } else {
+            format!("{inner}[]")
+        }
+    }
+
+    fn map_all(&self, types: &[RustType]) -> String {
+        types
+            .iter()
+            .map(|ty| self.map(ty))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Entry of `custom` for the type written as `segments`: an exact path match, or else the
+/// first full path key it ends with, or else the key naming its last segment. See
+/// [`type_path_matches`].
+pub(crate) fn custom_mapping<'a>(
+    custom: &'a BTreeMap<String, TypeMapping>,
+    segments: &[String],
+) -> Option<&'a TypeMapping> {
+    if let Some(mapping) = custom.get(&segments.join("::")) {
+        return Some(mapping);
+    }
+
+    let (paths, names): (Vec<_>, Vec<_>) = custom.iter().partition(|(key, _)| key.contains("::"));
+    paths
+        .into_iter()
+        .chain(names)
+        .find(|(key, _)| type_path_matches(key, segments))
+        .map(|(_, mapping)| mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_type;
+
+    fn ts(mapper: &TsTypeMapper, ty: &str) -> String {
+        mapper.map(&parse_type(ty).unwrap())
+    }
+
+    #[test]
+    fn maps_builtin_types() {
+        let mapper = TsTypeMapper::default();
+
+        assert_eq!(ts(&mapper, "i64"), "string");
+        assert_eq!(ts(&mapper, "u64"), "number");
+        assert_eq!(ts(&mapper, "f32"), "number");
+        assert_eq!(ts(&mapper, "&str"), "string");
+        assert_eq!(ts(&mapper, "uuid::Uuid"), "string");
+        assert_eq!(ts(&mapper, "bool"), "boolean");
+        assert_eq!(ts(&mapper, "()"), "null");
+        assert_eq!(ts(&mapper, "serde_json::Value"), "unknown");
+        assert_eq!(ts(&mapper, "Option<i32>"), "number | null");
+        assert_eq!(ts(&mapper, "Vec<Option<Task>>"), "(Task | null)[]");
+        assert_eq!(ts(&mapper, "[u8; 4]"), "number[]");
+        assert_eq!(ts(&mapper, "(String, bool)"), "[string, boolean]");
+        assert_eq!(
+            ts(&mapper, "HashMap<String, Vec<i64>>"),
+            "Record<string, string[]>"
+        );
+        assert_eq!(ts(&mapper, "Box<Arc<Task>>"), "Task");
+        assert_eq!(
+            ts(&mapper, "ParamsIded<TaskFilter>"),
+            "ParamsIded<TaskFilter>"
+        );
+    }
+
+    #[test]
+    fn custom_mappings_match_by_path_or_name() {
+        let mapper = TsTypeMapper::new(BTreeMap::from([
+            (
+                "time::OffsetDateTime".to_owned(),
+                TypeMapping::Ts("string".to_owned()),
+            ),
+            (
+                "Decimal".to_owned(),
+                TypeMapping::Table {
+                    ts: "Big".to_owned(),
+                    import: Some(crate::config::TypeImport {
+                        from: "big.js".to_owned(),
+                        name: None,
+                    }),
+                },
+            ),
+        ]));
+
+        assert_eq!(ts(&mapper, "time::OffsetDateTime"), "string");
+        assert_eq!(ts(&mapper, "::time::OffsetDateTime"), "string");
+        assert_eq!(ts(&mapper, "Option<rust_decimal::Decimal>"), "Big | null");
+        assert!(mapper.is_custom(&parse_type("Decimal").unwrap()));
+        assert!(!mapper.is_custom(&parse_type("Vec<Decimal>").unwrap()));
+
+        let types = [parse_type("Vec<Decimal>").unwrap()];
+        assert_eq!(
+            mapper.imports(&types),
+            BTreeMap::from([("big.js".to_owned(), BTreeSet::from(["Big".to_owned()]))])
+        );
+    }
+
+    #[test]
+    fn full_path_mappings_only_match_full_paths() {
+        let mapper = TsTypeMapper::new(BTreeMap::from([
+            (
+                "rpc::Error".to_owned(),
+                TypeMapping::Ts("RpcFault".to_owned()),
+            ),
+            ("Error".to_owned(), TypeMapping::Ts("AppError".to_owned())),
+        ]));
+
+        assert_eq!(ts(&mapper, "rpc::Error"), "RpcFault");
+        assert_eq!(ts(&mapper, "crate::rpc::Error"), "RpcFault");
+        assert_eq!(ts(&mapper, "Error"), "AppError");
+        assert_eq!(ts(&mapper, "model::Error"), "AppError");
+
+        let mapper = TsTypeMapper::new(BTreeMap::from([(
+            "rpc::Error".to_owned(),
+            TypeMapping::Ts("RpcFault".to_owned()),
+        )]));
+        assert_eq!(ts(&mapper, "Error"), "Error");
+        assert_eq!(ts(&mapper, "model::Error"), "Error");
+    }
+}
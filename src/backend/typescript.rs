@@ -0,0 +1,478 @@
+use crate::{
+    backend::{
+        param_structure, params_struct, result_error, result_payload, Backend, TsTypeMapper,
+    },
+    config::{GeneratorConfig, ParamStructure},
+    ir::{Method, RpcModel, RustType},
+    Error, Result,
+};
+
+/// The TypeScript client, one `<entity>_client` object per service.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TypeScript;
+
+impl Backend for TypeScript {
+    fn render(&self, model: &RpcModel, config: &GeneratorConfig) -> Result<String> {
+        let mapper = TsTypeMapper::new(config.type_mappings.clone());
+        let imports = create_import_statements(model, config, &mapper)?;
+        let mut clients = String::from("");
+        for service in model.services.iter() {
+            let client_name = &service.name;
+            let functions: String = service
+                .methods
+                .iter()
+                .map(|method| create_client_method(method, model, &mapper, config))
+                .collect::<Vec<String>>()
+                .join("\n");
+            if functions.is_empty() {
+                continue;
+            }
+            if !is_identifier(client_name) {
+                let method = &service.methods[0];
+                return Err(Error::InvalidClientName(format!(
+                    "{client_name}: client of handler {} ({}) isn't a JavaScript identifier",
+                    method.name, method.location
+                )));
+            }
+            clients += &format!(
+                "\n\nexport const {client_name}_client = {{\n{}\n}};\n",
+                functions
+            );
+        }
+
+        Ok(format!(
+            r#"//*********************************************************************************
+//***THIS FILE IS GENERATED AUTOMATICALLY AND WILL BE OVERWRITTEN. DO NOT MODIFY***
+//*********************************************************************************
+
+{}
+
+{}
+            "#,
+            imports, clients,
+        ))
+    }
+}
+
+fn create_import_statements(
+    model: &RpcModel,
+    config: &GeneratorConfig,
+    mapper: &TsTypeMapper,
+) -> Result<String> {
+    let types_import = config.types_import()?;
+    let mut imports = Vec::new();
+    imports.push(format!(
+        "import type {{{}}} from \"{types_import}/bindings\";",
+        model
+            .bindings
+            .iter()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ")
+    ));
+    imports.push(format!("export * from \"{types_import}\";"));
+    imports.push(format!(
+        "import {{ baseApiUrl, handleError }} from \"{}\"",
+        config.api_import
+    ));
+
+    imports.push(format!(
+        "import {{ Try, Err }} from \"{}\";",
+        config.oxy_import
+    ));
+
+    let used_types = model
+        .methods()
+        .flat_map(|(_, method)| method.params.iter().map(|p| &p.ty).chain([&method.result]));
+    for (from, names) in mapper.imports(used_types) {
+        let names = names.into_iter().collect::<Vec<String>>().join(", ");
+        imports.push(format!("import type {{ {names} }} from \"{from}\";"));
+    }
+
+    imports.push(
+        r#"
+export type ListOptions = {
+  limit?: number,
+  offset?: number,
+  order_bys?: string,
+};
+
+export type DataRpcResult<T> = {
+    data: T
+};
+
+type Option<T> = T | null;
+
+export type RpcResponse<R> = { id: string, jsonrpc: number, result: R };
+
+export type RpcResult<T> = RpcResponse<DataRpcResult<T>>;
+
+export type ClientErrorValue = {
+  data: {
+    detail: ClientError["detail"]
+    req_uuid: string
+  },
+  message: ClientError["message"]
+};
+
+export type HandlerErrorValue<E> = {
+  data: {
+    detail: E
+    req_uuid: string
+  },
+  message: string
+};
+
+export type RpcError<E = never> = {
+  id: string,
+  jsonrpc: number,
+  error: [E] extends [never] ? ClientErrorValue : HandlerErrorValue<E>
+};
+
+export type ParamsIded = { id: string };
+
+export type ParamsForCreate<T> = { data: T };
+
+export type ParamsForUpdate<T> = { id: string, data: T };
+
+export type ParamsList<T> = {
+  filters?: Partial<Record<keyof T, any>>[],
+  list_options?: {
+    limit?: number,
+    offset?: number,
+    order_bys?: string,
+  }
+};
+
+const reqConfig: RequestInit = {
+        method: "POST",
+        headers: {
+          "Content-Type": "application/json",
+        },
+        credentials: "include",
+      };
+"#
+        .to_owned(),
+    );
+
+    imports.push(format!(
+        "const rpcUrl = `${{baseApiUrl}}{}`;\n",
+        config.rpc_endpoint
+    ));
+
+    Ok(imports.join("\n"))
+}
+
+fn create_client_method(
+    method: &Method,
+    model: &RpcModel,
+    mapper: &TsTypeMapper,
+    config: &GeneratorConfig,
+) -> String {
+    let handler_name = js_property_name(&method.name);
+    let rpc_name = &method.rpc_name;
+
+    let params_struct = params_struct(method, model).is_some();
+    let client_params = client_params(method, params_struct);
+    let signature = client_params
+        .iter()
+        .zip(&method.params)
+        .map(|(name, param)| format!("{name}: {}", mapper.map(&param.ty)))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let params = match (
+        client_params.as_slice(),
+        param_structure(method, model, config.param_structure),
+    ) {
+        ([], _) => String::new(),
+        ([name], ParamStructure::ByName) if params_struct => format!(
+            r#"
+          params: {name},"#
+        ),
+        (names, ParamStructure::ByName) => format!(
+            r#"
+          params: {{ {} }},"#,
+            names.join(", ")
+        ),
+        (names, ParamStructure::ByPosition) => format!(
+            r#"
+          params: [{}],"#,
+            names.join(", ")
+        ),
+    };
+
+    let client_return_type = client_return_type(&method.result, mapper, config);
+    let client_error_type = client_error_type(&method.result, model, mapper);
+
+    format!(
+        r#"{}    async {handler_name}({signature}) {{
+      const happyPath = async () => fetch(rpcUrl, {{
+        ...reqConfig,
+        body: JSON.stringify({{
+          id: 1,
+          jsonrpc: "2.0",
+          method: "{rpc_name}",{params}
+        }}),
+      }}) as unknown as Promise<{client_return_type}>;
+      const val = await Try(happyPath, (e: {client_error_type}) => Err(e));
+      if (val.isError && handleError){{
+          handleError(val);
+      }}
+      return val;
+    }},
+"#,
+        doc_comment(&method.docs)
+    )
+}
+
+/// `RpcResult<T>` for payloads sent under `data`, like `DataRpcResult`'s, `RpcResponse`
+/// of the payload in its envelopes' fields otherwise.
+fn client_return_type(
+    result: &RustType,
+    mapper: &TsTypeMapper,
+    config: &GeneratorConfig,
+) -> String {
+    let (fields, payload) = result_payload(result, &config.envelopes);
+    let payload = mapper.map(payload);
+
+    match fields.as_slice() {
+        ["data"] => format!("RpcResult<{payload}>"),
+        fields => {
+            let result = fields
+                .iter()
+                .rev()
+                .fold(payload, |inner, field| format!("{{ {field}: {inner} }}"));
+            format!("RpcResponse<{result}>")
+        }
+    }
+}
+
+/// `RpcError`, or when the handler returns a `Result<T, E>` whose `E` the client knows about
+/// from the bindings or a type mapping, the union of the handler's error, serialized as the
+/// error's `detail`, and `RpcError` for the errors raised before reaching it.
+fn client_error_type(result: &RustType, model: &RpcModel, mapper: &TsTypeMapper) -> String {
+    match result_error(result) {
+        Some(error)
+            if error
+                .name()
+                .is_some_and(|name| model.bindings.contains(name))
+                || mapper.is_custom(error) =>
+        {
+            format!("RpcError<{}> | RpcError", mapper.map(error))
+        }
+        _ => "RpcError".to_owned(),
+    }
+}
+
+/// Names of the client method's parameters. A `params_struct` is `params`, other
+/// parameters keep their Rust names when those are valid and distinct.
+fn client_params(method: &Method, params_struct: bool) -> Vec<String> {
+    if params_struct {
+        return vec!["params".to_owned()];
+    }
+
+    let mut names: Vec<String> = vec![];
+    for (i, param) in method.params.iter().enumerate() {
+        let name = param.name.as_str();
+        let name = match is_identifier(name) && !names.iter().any(|n| n == name) {
+            true => name.to_owned(),
+            false => format!("param{i}"),
+        };
+        names.push(name);
+    }
+    names
+}
+
+/// `name` as a method name in an object literal, quoted unless it is an identifier.
+fn js_property_name(name: &str) -> String {
+    match is_identifier(name) {
+        true => name.to_owned(),
+        false => format!("{name:?}"),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+fn doc_comment(docs: &[String]) -> String {
+    if docs.is_empty() {
+        return String::new();
+    }
+
+    let lines = docs
+        .iter()
+        .map(|line| format!("     * {line}\n").replace("* \n", "*\n"))
+        .collect::<String>();
+    format!("    /**\n{lines}     */\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::test_model::{method, model, types},
+        config::{Envelope, TypeMapping},
+    };
+
+    fn render(client: &str, methods: Vec<Method>) -> Result<String> {
+        TypeScript.render(&model(client, methods), &GeneratorConfig::new("/app"))
+    }
+
+    #[test]
+    fn rejects_clients_that_arent_identifiers() {
+        let list = || vec![method("list", &[], "Result<DataRpcResult<Vec<Task>>>")];
+
+        assert!(render("task", list())
+            .unwrap()
+            .contains("export const task_client = {"));
+        match render("task-admin", list()) {
+            Err(Error::InvalidClientName(message)) => {
+                assert!(
+                    message.contains("handler list (task_rpc.rs:1)"),
+                    "{message}"
+                )
+            }
+            result => panic!("expected an invalid client name, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn sends_params_by_count() {
+        let methods = vec![
+            method("ping", &[], "Result<()>"),
+            method("get", &[("params", "ParamsIded")], "Result<Task>"),
+            method("get_by_id", &[("id", "i64")], "Result<Task>"),
+            method(
+                "rename",
+                &[("id", "i64"), ("title", "String")],
+                "Result<Task>",
+            ),
+        ];
+        let model = RpcModel {
+            types: types("pub struct ParamsIded { pub id: i64 }"),
+            ..model("task", methods)
+        };
+        let client = TypeScript
+            .render(&model, &GeneratorConfig::new("/app"))
+            .unwrap();
+
+        assert!(client.contains("async ping() {"));
+        assert!(client.contains("method: \"ping\",\n        }),"));
+        assert!(client.contains("async get(params: ParamsIded) {"));
+        assert!(client.contains("params: params,"));
+        assert!(client.contains("async get_by_id(id: string) {"));
+        assert!(client.contains("params: { id },"));
+        assert!(client.contains("async rename(id: string, title: string) {"));
+        assert!(client.contains("params: { id, title },"));
+    }
+
+    #[test]
+    fn sends_params_by_position() {
+        let patterns = render(
+            "task",
+            vec![method(
+                "swap",
+                &[("(a, b)", "(i64, i64)"), ("a", "u8")],
+                "Result<Task>",
+            )],
+        )
+        .unwrap();
+        assert!(patterns.contains("async swap(param0: [string, string], a: number) {"));
+        assert!(patterns.contains("params: [param0, a],"));
+
+        let config = GeneratorConfig {
+            param_structure: ParamStructure::ByPosition,
+            ..GeneratorConfig::new("/app")
+        };
+        let methods = vec![
+            method("get", &[("params", "ParamsIded")], "Result<Task>"),
+            method(
+                "rename",
+                &[("id", "i64"), ("title", "String")],
+                "Result<Task>",
+            ),
+        ];
+        let configured = TypeScript.render(&model("task", methods), &config).unwrap();
+        assert!(configured.contains("params: [params],"));
+        assert!(configured.contains("params: [id, title],"));
+    }
+
+    #[test]
+    fn types_handler_errors() {
+        let methods = vec![
+            method("get", &[], "Result<Task, TaskError>"),
+            method("list", &[], "Result<Vec<Task>, UnknownError>"),
+            method("count", &[], "Result<u32, rpc::Error>"),
+            method("create", &[], "Result<Task>"),
+        ];
+        let model = RpcModel {
+            bindings: ["TaskError".to_owned()].into(),
+            ..model("task", methods)
+        };
+        let config = GeneratorConfig {
+            type_mappings: [(
+                "rpc::Error".to_owned(),
+                TypeMapping::Ts("ServerError".to_owned()),
+            )]
+            .into(),
+            ..GeneratorConfig::new("/app")
+        };
+        let client = TypeScript.render(&model, &config).unwrap();
+
+        let errors: Vec<&str> = client
+            .lines()
+            .filter_map(|line| line.split_once("(e: ")?.1.split_once(") =>"))
+            .map(|(error, _)| error)
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "RpcError<TaskError> | RpcError",
+                "RpcError",
+                "RpcError<ServerError> | RpcError",
+                "RpcError",
+            ]
+        );
+    }
+
+    #[test]
+    fn return_types_with_and_without_envelopes() {
+        let methods = vec![
+            method("get", &[], "Result<DataRpcResult<Task>>"),
+            method("json", &[], "Result<Json<Task>>"),
+            method("plain", &[], "Task"),
+            method("delete", &[], "Result<()>"),
+            method("page", &[], "Result<Json<Page<Task>>>"),
+        ];
+        let mut config = GeneratorConfig::new("/app");
+        config.envelopes.push(Envelope::Field {
+            name: "Page".to_owned(),
+            field: "items".to_owned(),
+        });
+        let client = TypeScript.render(&model("task", methods), &config).unwrap();
+
+        let results: Vec<&str> = client
+            .lines()
+            .filter_map(|line| {
+                line.split_once("as unknown as Promise<")?
+                    .1
+                    .strip_suffix(">;")
+            })
+            .collect();
+        assert_eq!(
+            results,
+            [
+                "RpcResult<Task>",
+                "RpcResponse<Task>",
+                "RpcResponse<Task>",
+                "RpcResponse<null>",
+                "RpcResponse<{ items: Task }>",
+            ]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ir::{Method, Service, TypeDef},
+    Error, Result,
+};
+
+/// Name of the cache of scans under rules with key `rules`. Each set of rules gets a file of
+/// its own, so profiles scanning different roots, or the same one with different rules, don't
+/// evict each other.
+fn cache_file_name(rules: &str) -> String {
+    format!("scan_cache_{:016x}.json", content_hash(rules.as_bytes()))
+}
+
+/// What a scan takes from a single file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct FileScan {
+    pub services: Vec<Service>,
+    pub type_defs: Vec<TypeDef>,
+    pub bindings: Vec<String>,
+    /// Handlers defined in the file and registered by it, under their own name or not.
+    pub registered: BTreeSet<String>,
+    /// Methods of the handlers defined in the file that it neither registers nor marks, in
+    /// case another module registers them.
+    pub unregistered: Vec<Method>,
+    /// Handlers of other modules the file registers.
+    pub foreign_names: Vec<ForeignName>,
+}
+
+/// A handler of another module registered by path or import, as in
+/// `.append_dyn("tasks.list", task_rpc::list.into_dyn())`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ForeignName {
+    /// Path of the handler's module relative to the registering file, e.g. `["task_rpc"]`.
+    pub module: Vec<String>,
+    pub handler: String,
+    /// JSON-RPC method name when given explicitly.
+    pub rpc_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFile {
+    hash: u64,
+    scan: FileScan,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    /// Version of client_gen that wrote the cache, a different one discards it.
+    version: String,
+    /// Key of the rpc file rules the files were scanned with, in case another key's file name
+    /// hashes the same.
+    rules: String,
+    files: BTreeMap<PathBuf, CachedFile>,
+}
+
+/// Per-file scan results from the previous run, keyed by the hash of the file's content.
+#[derive(Debug, Default)]
+pub(crate) struct ScanCache {
+    dir: Option<PathBuf>,
+    rules: String,
+    previous: BTreeMap<PathBuf, CachedFile>,
+    /// Entries of the files seen by this run, the only ones saved.
+    current: BTreeMap<PathBuf, CachedFile>,
+}
+
+impl ScanCache {
+    /// The cache stored in `dir`, or an empty one when there is none or it can't be read.
+    /// Without a `dir` nothing is ever cached.
+    pub fn load(dir: Option<&Path>, rules: &str) -> Self {
+        let previous = dir
+            .and_then(|dir| fs::read(dir.join(cache_file_name(rules))).ok())
+            .and_then(|content| serde_json::from_slice::<CacheFile>(&content).ok())
+            .filter(|cache| cache.version == env!("CARGO_PKG_VERSION") && cache.rules == rules)
+            .map(|cache| cache.files)
+            .unwrap_or_default();
+
+        ScanCache {
+            dir: dir.map(Path::to_path_buf),
+            rules: rules.to_owned(),
+            previous,
+            current: BTreeMap::new(),
+        }
+    }
+
+    /// The scan of `path` from the previous run, if its content still hashes to `hash`.
+    pub fn get(&self, path: &Path, hash: u64) -> Option<FileScan> {
+        self.previous
+            .get(path)
+            .filter(|cached| cached.hash == hash)
+            .map(|cached| cached.scan.clone())
+    }
+
+    /// Records the scan of `path` for the next run.
+    pub fn insert(&mut self, path: PathBuf, hash: u64, scan: FileScan) {
+        if self.dir.is_some() {
+            self.current.insert(path, CachedFile { hash, scan });
+        }
+    }
+
+    pub fn save(self) -> Result<()> {
+        let Some(dir) = self.dir else {
+            return Ok(());
+        };
+
+        let file_name = cache_file_name(&self.rules);
+        let cache = CacheFile {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            rules: self.rules,
+            files: self.current,
+        };
+        let content = serde_json::to_vec(&cache).map_err(|e| Error::Serialize(e.to_string()))?;
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(file_name), content)?;
+
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a, stable across runs and Rust versions unlike `DefaultHasher`.
+pub(crate) fn content_hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for each test.
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("client_gen_cache_{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn scan(binding: &str) -> FileScan {
+        FileScan {
+            bindings: vec![binding.to_owned()],
+            ..Default::default()
+        }
+    }
+
+    fn cached_bindings(cache: &ScanCache, path: &str, hash: u64) -> Option<Vec<String>> {
+        cache.get(Path::new(path), hash).map(|scan| scan.bindings)
+    }
+
+    #[test]
+    fn reuses_scans_of_unchanged_files() {
+        let dir = cache_dir("unchanged");
+        let mut cache = ScanCache::load(Some(&dir), "rules");
+        cache.insert(PathBuf::from("task_rpc.rs"), 1, scan("Task"));
+        cache.save().unwrap();
+
+        let cache = ScanCache::load(Some(&dir), "rules");
+        assert_eq!(
+            cached_bindings(&cache, "task_rpc.rs", 1),
+            Some(vec!["Task".to_owned()])
+        );
+        assert_eq!(cached_bindings(&cache, "task_rpc.rs", 2), None);
+        assert_eq!(cached_bindings(&cache, "note_rpc.rs", 1), None);
+    }
+
+    #[test]
+    fn keeps_scans_of_other_rules_apart() {
+        let dir = cache_dir("rules");
+        for (rules, binding) in [("rules", "Task"), ("other rules", "Note")] {
+            let mut cache = ScanCache::load(Some(&dir), rules);
+            assert_eq!(cached_bindings(&cache, "task_rpc.rs", 1), None);
+            cache.insert(PathBuf::from("task_rpc.rs"), 1, scan(binding));
+            cache.save().unwrap();
+        }
+
+        for (rules, binding) in [("rules", "Task"), ("other rules", "Note")] {
+            let cache = ScanCache::load(Some(&dir), rules);
+            assert_eq!(
+                cached_bindings(&cache, "task_rpc.rs", 1),
+                Some(vec![binding.to_owned()])
+            );
+        }
+    }
+
+    #[test]
+    fn only_saves_files_seen_by_the_run() {
+        let dir = cache_dir("seen");
+        let mut cache = ScanCache::load(Some(&dir), "rules");
+        cache.insert(PathBuf::from("task_rpc.rs"), 1, scan("Task"));
+        cache.insert(PathBuf::from("note_rpc.rs"), 1, scan("Note"));
+        cache.save().unwrap();
+
+        let mut cache = ScanCache::load(Some(&dir), "rules");
+        cache.insert(PathBuf::from("task_rpc.rs"), 1, scan("Task"));
+        cache.save().unwrap();
+
+        let cache = ScanCache::load(Some(&dir), "rules");
+        assert!(cached_bindings(&cache, "task_rpc.rs", 1).is_some());
+        assert_eq!(cached_bindings(&cache, "note_rpc.rs", 1), None);
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use client_gen::{OutputFormat, ProfileConfig, Result};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "client_gen",
+    version,
+    about = "Generates a TypeScript client for the rpc-router handlers of a Rust backend"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run typeshare and write the generated client
+    Generate(GenerateArgs),
+    /// Check the written client is up to date without writing anything, printing a diff and
+    /// exiting non-zero when it isn't
+    Check(DirArgs),
+    /// Regenerate the clients whenever a Rust file or `bindings.ts` changes
+    Watch(DirArgs),
+    /// Print every handler found, grouped by entity
+    ListHandlers(DirArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct DirArgs {
+    /// Config file to use instead of the nearest `client_gen.toml`
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Profile from the config file to run, can be repeated [default: all profiles]
+    #[arg(long = "profile")]
+    pub profiles: Vec<String>,
+
+    /// Directory scanned for `_rpc.rs` and `bindings.ts` files [default: .]
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+
+    /// Directory typeshare writes `bindings.ts` into [default: frontend/src/lib/types]
+    #[arg(long)]
+    pub types_dir: Option<PathBuf>,
+
+    /// Directory the generated client is written into [default: frontend/src/lib/api/client]
+    #[arg(long)]
+    pub client_dir: Option<PathBuf>,
+
+    /// File name of the generated client, relative to the client dir [default: generated_client.ts]
+    #[arg(long)]
+    pub output_file: Option<PathBuf>,
+
+    /// What to generate [default: typescript]
+    #[arg(long)]
+    pub format: Option<OutputFormat>,
+
+    /// Only scan the files matching this glob, can be repeated
+    #[arg(long)]
+    pub include: Vec<String>,
+
+    /// Don't scan files or directories matching this gitignore-style glob, can be repeated
+    #[arg(long)]
+    pub exclude: Vec<String>,
+
+    /// Parse every file instead of reusing the scan results of unchanged ones
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
+    #[command(flatten)]
+    pub dirs: DirArgs,
+
+    /// Don't run typeshare before generating
+    #[arg(long)]
+    pub skip_typeshare: bool,
+
+    /// Same as `client_gen check`: compare the client with the existing file instead of
+    /// writing it, printing a diff and exiting non-zero when it is out of date
+    #[arg(long)]
+    pub check: bool,
+}
+
+impl DirArgs {
+    /// Settings passed on the command line, which win over the config file.
+    pub fn overrides(&self) -> Result<ProfileConfig> {
+        let absolute = |p: &Option<PathBuf>| p.as_deref().map(std::path::absolute).transpose();
+
+        Ok(ProfileConfig {
+            root: absolute(&self.root)?,
+            types_dir: absolute(&self.types_dir)?,
+            client_dir: absolute(&self.client_dir)?,
+            output_file: self.output_file.clone(),
+            format: self.format,
+            cache: self.no_cache.then_some(false),
+            include: (!self.include.is_empty()).then(|| self.include.clone()),
+            exclude: (!self.exclude.is_empty()).then(|| self.exclude.clone()),
+            ..Default::default()
+        })
+    }
+}
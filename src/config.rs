@@ -0,0 +1,587 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    process_rpc::{DEFAULT_ENTITY_PATTERN, DEFAULT_RESOURCE_TYPES, DEFAULT_RPC_FILES},
+    util::relative_import_path,
+    Error, Result,
+};
+
+pub const CONFIG_FILE_NAME: &str = "client_gen.toml";
+
+/// Settings of a single generated client. Every field is optional so that profiles can
+/// fall back to the top level of the config file, and the config file to the CLI defaults.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub root: Option<PathBuf>,
+    pub types_dir: Option<PathBuf>,
+    pub client_dir: Option<PathBuf>,
+    pub output_file: Option<PathBuf>,
+    pub format: Option<OutputFormat>,
+    pub api_import: Option<String>,
+    pub oxy_import: Option<String>,
+    pub rpc_endpoint: Option<String>,
+    pub method_order: Option<MethodOrder>,
+    pub param_structure: Option<ParamStructure>,
+    /// Whether to cache scan results between runs, on by default.
+    pub cache: Option<bool>,
+    pub cache_dir: Option<PathBuf>,
+    /// Globs of the files to scan, relative to the root [default: all of them].
+    pub include: Option<Vec<String>>,
+    /// Gitignore-style globs of files and directories not to scan.
+    pub exclude: Option<Vec<String>>,
+    /// Globs of the files holding handlers, relative to the root or full paths.
+    pub rpc_files: Option<Vec<String>>,
+    /// Regex naming the client of an rpc file after its `entity` group.
+    pub entity_pattern: Option<String>,
+    /// Types of handler params provided by the server, left out of the client, on top of
+    /// `Ctx`, `ModelManager` and `State`.
+    pub resource_types: Option<Vec<String>>,
+    /// Types wrapping the payload of handler results, peeled off to find it, on top of
+    /// `DataRpcResult` and `Json`. An envelope named like a default one replaces it.
+    pub envelopes: Option<Vec<Envelope>>,
+    /// Extra Rust to TypeScript type mappings, keyed by type name, matching the types of that
+    /// name, or by full path, matching the types written with a path ending in it.
+    pub types: BTreeMap<String, TypeMapping>,
+}
+
+impl ProfileConfig {
+    /// Fields set on `self` win, the rest are taken from `fallback`. Type mappings of both are
+    /// kept, `self` winning for types mapped by both.
+    pub fn or(self, fallback: &ProfileConfig) -> ProfileConfig {
+        let fallback = fallback.clone();
+        let mut types = fallback.types;
+        types.extend(self.types);
+        ProfileConfig {
+            root: self.root.or(fallback.root),
+            types_dir: self.types_dir.or(fallback.types_dir),
+            client_dir: self.client_dir.or(fallback.client_dir),
+            output_file: self.output_file.or(fallback.output_file),
+            format: self.format.or(fallback.format),
+            api_import: self.api_import.or(fallback.api_import),
+            oxy_import: self.oxy_import.or(fallback.oxy_import),
+            rpc_endpoint: self.rpc_endpoint.or(fallback.rpc_endpoint),
+            method_order: self.method_order.or(fallback.method_order),
+            param_structure: self.param_structure.or(fallback.param_structure),
+            cache: self.cache.or(fallback.cache),
+            cache_dir: self.cache_dir.or(fallback.cache_dir),
+            include: self.include.or(fallback.include),
+            exclude: self.exclude.or(fallback.exclude),
+            rpc_files: self.rpc_files.or(fallback.rpc_files),
+            entity_pattern: self.entity_pattern.or(fallback.entity_pattern),
+            resource_types: self.resource_types.or(fallback.resource_types),
+            envelopes: self.envelopes.or(fallback.envelopes),
+            types,
+        }
+    }
+}
+
+/// What a profile generates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The TypeScript client
+    #[default]
+    #[value(name = "typescript")]
+    TypeScript,
+    /// An OpenRPC document describing the handlers
+    #[value(name = "openrpc")]
+    OpenRpc,
+    /// JSON Schema definitions of the params and result types
+    #[value(name = "jsonschema")]
+    JsonSchema,
+}
+
+impl OutputFormat {
+    /// File name of the output when the profile doesn't set `output_file`.
+    pub fn default_file_name(&self) -> &'static str {
+        match self {
+            OutputFormat::TypeScript => "generated_client.ts",
+            OutputFormat::OpenRpc => "openrpc.json",
+            OutputFormat::JsonSchema => "schemas.json",
+        }
+    }
+}
+
+/// Order of the methods within each client object. Services are always sorted by name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MethodOrder {
+    /// The order handlers are registered in their rpc file, marked handlers last
+    #[default]
+    Source,
+    /// By client method name
+    Alphabetical,
+}
+
+/// How the params of handlers taking several of them are sent, as in the `paramStructure`
+/// of OpenRPC. Handlers without params send none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParamStructure {
+    /// An object keyed by parameter name, or the only parameter itself when it is a struct
+    #[default]
+    ByName,
+    /// An array of the parameters in order
+    ByPosition,
+}
+
+/// A type wrapping the payload of handler results, its first generic argument. Either just
+/// the type's name or full path, for wrappers serialized as the payload itself:
+///
+/// ```toml
+/// envelopes = ["Json", { name = "DataRpcResult", field = "data" }]
+/// ```
+///
+/// or a table that also names the field the payload is serialized under.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Envelope {
+    Transparent(String),
+    Field { name: String, field: String },
+}
+
+impl Envelope {
+    pub fn name(&self) -> &str {
+        match self {
+            Envelope::Transparent(name) | Envelope::Field { name, .. } => name,
+        }
+    }
+
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            Envelope::Transparent(_) => None,
+            Envelope::Field { field, .. } => Some(field),
+        }
+    }
+}
+
+/// Value of a `[types]` entry, either just the TypeScript type:
+///
+/// ```toml
+/// [types]
+/// "time::OffsetDateTime" = "string"
+/// ```
+///
+/// or a table that also names the module the type is imported from:
+///
+/// ```toml
+/// [types."rust_decimal::Decimal"]
+/// ts = "Decimal"
+/// import = { from = "decimal.js" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum TypeMapping {
+    Ts(String),
+    Table {
+        ts: String,
+        import: Option<TypeImport>,
+    },
+}
+
+impl TypeMapping {
+    pub fn ts(&self) -> &str {
+        match self {
+            TypeMapping::Ts(ts) | TypeMapping::Table { ts, .. } => ts,
+        }
+    }
+
+    pub fn import(&self) -> Option<&TypeImport> {
+        match self {
+            TypeMapping::Ts(_) => None,
+            TypeMapping::Table { import, .. } => import.as_ref(),
+        }
+    }
+}
+
+/// `import type { <name> } from "<from>"`, added to the client when the mapping is used.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TypeImport {
+    pub from: String,
+    /// Name to import, defaults to the mapped TypeScript type.
+    pub name: Option<String>,
+}
+
+/// Contents of a `client_gen.toml`. Top-level settings apply to every profile.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Directory relative paths in the config are resolved against.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+
+    #[serde(flatten)]
+    pub defaults: ProfileConfig,
+
+    pub profiles: BTreeMap<String, ProfileConfig>,
+
+    /// Top-level keys that are neither profile settings nor `profiles`, rejected on load.
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// Loads `path`, or the first `client_gen.toml` found walking up from the current
+    /// directory. Without a config file every profile setting comes from the CLI defaults.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => find_config_file(&env::current_dir()?),
+        };
+
+        let Some(path) = path else {
+            return Ok(Config {
+                base_dir: env::current_dir()?,
+                ..Default::default()
+            });
+        };
+
+        let content = fs::read_to_string(&path)?;
+        let mut config = Config::parse(&content)
+            .map_err(|e| Error::InvalidConfig(format!("{}: {e}", path.display())))?;
+        config.base_dir = std::path::absolute(&path)?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        Ok(config)
+    }
+
+    /// Contents of a config file, failing on keys it doesn't know, as they're likely typos.
+    fn parse(content: &str) -> std::result::Result<Self, String> {
+        let config: Config = toml::from_str(content).map_err(|e| e.to_string())?;
+        match config.unknown.keys().next() {
+            Some(key) => Err(format!("unknown field `{key}`")),
+            None => Ok(config),
+        }
+    }
+
+    /// Resolves the selected profiles (all of them when `selected` is empty), with
+    /// `overrides` taking precedence over anything in the file.
+    pub fn profiles(
+        &self,
+        selected: &[String],
+        overrides: &ProfileConfig,
+    ) -> Result<Vec<GeneratorConfig>> {
+        if self.profiles.is_empty() {
+            if let Some(name) = selected.first() {
+                return Err(Error::UnknownProfile(name.to_owned()));
+            }
+            let profile = overrides.clone().or(&self.defaults);
+            return Ok(vec![self.resolve("default", profile)]);
+        }
+
+        let names: Vec<&String> = if selected.is_empty() {
+            self.profiles.keys().collect()
+        } else {
+            selected.iter().collect()
+        };
+
+        names
+            .into_iter()
+            .map(|name| {
+                let profile = self
+                    .profiles
+                    .get(name)
+                    .ok_or(Error::UnknownProfile(name.to_owned()))?;
+                let profile = overrides.clone().or(&profile.clone().or(&self.defaults));
+                Ok(self.resolve(name, profile))
+            })
+            .collect()
+    }
+
+    fn resolve(&self, name: &str, profile: ProfileConfig) -> GeneratorConfig {
+        let defaults = GeneratorConfig::new(&self.base_dir);
+        let format = profile.format.unwrap_or_default();
+        let path =
+            |p: Option<PathBuf>, default: PathBuf| p.map_or(default, |p| self.base_dir.join(p));
+        let root = path(profile.root, defaults.root);
+
+        GeneratorConfig {
+            name: name.to_owned(),
+            cache_dir: match profile.cache {
+                Some(false) => None,
+                _ => Some(path(profile.cache_dir, root.join("target/client_gen"))),
+            },
+            root,
+            types_dir: path(profile.types_dir, defaults.types_dir),
+            client_dir: path(profile.client_dir, defaults.client_dir),
+            format,
+            output_file: profile
+                .output_file
+                .unwrap_or(PathBuf::from(format.default_file_name())),
+            api_import: profile.api_import.unwrap_or(defaults.api_import),
+            oxy_import: profile.oxy_import.unwrap_or(defaults.oxy_import),
+            rpc_endpoint: profile.rpc_endpoint.unwrap_or(defaults.rpc_endpoint),
+            method_order: profile.method_order.unwrap_or_default(),
+            param_structure: profile.param_structure.unwrap_or_default(),
+            include: profile.include.unwrap_or_default(),
+            exclude: profile.exclude.unwrap_or_default(),
+            rpc_files: profile.rpc_files.unwrap_or(defaults.rpc_files),
+            entity_pattern: profile.entity_pattern.unwrap_or(defaults.entity_pattern),
+            resource_types: defaults
+                .resource_types
+                .into_iter()
+                .chain(profile.resource_types.unwrap_or_default())
+                .fold(vec![], |mut types, ty| {
+                    if !types.contains(&ty) {
+                        types.push(ty);
+                    }
+                    types
+                }),
+            envelopes: {
+                // Configured envelopes come first, so they win over defaults of the same name.
+                let mut envelopes = profile.envelopes.unwrap_or_default();
+                envelopes.extend(defaults.envelopes);
+                envelopes
+            },
+            type_mappings: profile.types,
+        }
+    }
+}
+
+/// Everything needed to generate one client. Built from a profile of the config file, or
+/// directly with [`GeneratorConfig::new`] when embedding the generator.
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    /// Profile this config was resolved from, `default` otherwise.
+    pub name: String,
+    /// Directory scanned for `_rpc.rs` and `bindings.ts` files.
+    pub root: PathBuf,
+    /// Directory typeshare writes `bindings.ts` into.
+    pub types_dir: PathBuf,
+    /// Directory the generated client is written into.
+    pub client_dir: PathBuf,
+    /// File name of the generated client, relative to `client_dir`.
+    pub output_file: PathBuf,
+    /// What is written to `output_file`.
+    pub format: OutputFormat,
+    /// Module `baseApiUrl` and `handleError` are imported from.
+    pub api_import: String,
+    /// Module `Try` and `Err` are imported from.
+    pub oxy_import: String,
+    /// Path of the JSON-RPC endpoint, appended to `baseApiUrl`.
+    pub rpc_endpoint: String,
+    /// Order of the methods within each client object.
+    pub method_order: MethodOrder,
+    /// How handlers taking several params get them sent.
+    pub param_structure: ParamStructure,
+    /// Directory scan results are cached in between runs, `None` to always scan every file.
+    pub cache_dir: Option<PathBuf>,
+    /// Globs of the files scanned below `root`, every file when empty.
+    pub include: Vec<String>,
+    /// Gitignore-style globs skipped below `root`, on top of `.gitignore`d files, `target`
+    /// and `node_modules`.
+    pub exclude: Vec<String>,
+    /// Globs of the files holding handlers, relative to `root` or full paths.
+    pub rpc_files: Vec<String>,
+    /// Regex searched for in the path of an rpc file relative to `root`. Its `entity` group,
+    /// or first group, names the client the file's handlers go into.
+    pub entity_pattern: String,
+    /// Types of the handler params the server provides, like `Ctx` or axum's `State`, left
+    /// out of the client. Matched by name or full path, through references.
+    pub resource_types: Vec<String>,
+    /// Wrappers peeled off handler results to find their payload, `DataRpcResult` and
+    /// `Json` by default.
+    pub envelopes: Vec<Envelope>,
+    /// Rust to TypeScript mappings taking precedence over the built-in ones.
+    pub type_mappings: BTreeMap<String, TypeMapping>,
+}
+
+impl GeneratorConfig {
+    /// Config with the conventional frontend layout below `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+
+        GeneratorConfig {
+            name: "default".to_owned(),
+            types_dir: root.join("frontend/src/lib/types"),
+            client_dir: root.join("frontend/src/lib/api/client"),
+            output_file: PathBuf::from(OutputFormat::TypeScript.default_file_name()),
+            format: OutputFormat::TypeScript,
+            api_import: ".".to_owned(),
+            oxy_import: "@eman/oxy".to_owned(),
+            rpc_endpoint: "/api/rpc".to_owned(),
+            method_order: MethodOrder::Source,
+            param_structure: ParamStructure::ByName,
+            type_mappings: BTreeMap::new(),
+            cache_dir: Some(root.join("target/client_gen")),
+            include: vec![],
+            exclude: vec![],
+            rpc_files: DEFAULT_RPC_FILES.iter().map(|g| g.to_string()).collect(),
+            entity_pattern: DEFAULT_ENTITY_PATTERN.to_owned(),
+            resource_types: DEFAULT_RESOURCE_TYPES
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+            envelopes: vec![
+                Envelope::Field {
+                    name: "DataRpcResult".to_owned(),
+                    field: "data".to_owned(),
+                },
+                Envelope::Transparent("Json".to_owned()),
+            ],
+            root,
+        }
+    }
+
+    /// Path the client is written to.
+    pub fn output_path(&self) -> PathBuf {
+        self.client_dir.join(&self.output_file)
+    }
+
+    /// Import specifier of `types_dir` from the client.
+    pub fn types_import(&self) -> Result<String> {
+        relative_import_path(&self.client_dir, &self.types_dir)
+    }
+}
+
+fn find_config_file(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        Config {
+            base_dir: PathBuf::from("/app"),
+            ..Config::parse(toml).unwrap()
+        }
+    }
+
+    #[test]
+    fn profiles_fall_back_to_the_top_level() {
+        let config = config(
+            r#"
+            root = "backend"
+            rpc_endpoint = "/rpc"
+
+            [types]
+            Decimal = "string"
+
+            [profiles.web]
+            client_dir = "web/src/client"
+
+            [profiles.admin]
+            rpc_endpoint = "/admin/rpc"
+            format = "openrpc"
+
+            [profiles.admin.types]
+            Decimal = "number"
+            "#,
+        );
+        let overrides = ProfileConfig {
+            types_dir: Some(PathBuf::from("/types")),
+            ..Default::default()
+        };
+
+        let profiles = config.profiles(&[], &overrides).unwrap();
+        let [admin, web] = profiles.as_slice() else {
+            panic!("expected two profiles, got {profiles:?}");
+        };
+
+        assert_eq!(admin.name, "admin");
+        assert_eq!(admin.root, Path::new("/app/backend"));
+        assert_eq!(admin.rpc_endpoint, "/admin/rpc");
+        assert_eq!(
+            admin.output_path(),
+            Path::new("/app/frontend/src/lib/api/client/openrpc.json")
+        );
+        assert_eq!(admin.types_dir, Path::new("/types"));
+        assert_eq!(admin.type_mappings["Decimal"].ts(), "number");
+        assert_eq!(
+            admin.cache_dir.as_deref(),
+            Some(Path::new("/app/backend/target/client_gen"))
+        );
+
+        assert_eq!(web.rpc_endpoint, "/rpc");
+        assert_eq!(web.client_dir, Path::new("/app/web/src/client"));
+        assert_eq!(web.format, OutputFormat::TypeScript);
+        assert_eq!(web.types_dir, Path::new("/types"));
+        assert_eq!(web.type_mappings["Decimal"].ts(), "string");
+    }
+
+    #[test]
+    fn selects_profiles_by_name() {
+        let config = config("[profiles.web]\n[profiles.admin]");
+        let names = |selected: &[&str]| {
+            let selected: Vec<String> = selected.iter().map(|s| s.to_string()).collect();
+            config
+                .profiles(&selected, &ProfileConfig::default())
+                .map(|profiles| profiles.into_iter().map(|p| p.name).collect::<Vec<_>>())
+        };
+
+        assert_eq!(names(&["web"]).unwrap(), ["web"]);
+        assert!(matches!(names(&["mobile"]), Err(Error::UnknownProfile(name)) if name == "mobile"));
+
+        let config = Config {
+            base_dir: PathBuf::from("/app"),
+            ..Default::default()
+        };
+        let profiles = config.profiles(&[], &ProfileConfig::default()).unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "default");
+        assert_eq!(profiles[0].root, Path::new("/app"));
+        assert!(matches!(
+            config.profiles(&["web".to_owned()], &ProfileConfig::default()),
+            Err(Error::UnknownProfile(_))
+        ));
+    }
+
+    #[test]
+    fn resource_types_and_envelopes_extend_the_defaults() {
+        let config = config(
+            r#"
+            resource_types = ["Ctx", "Session"]
+            envelopes = ["Paged", { name = "DataRpcResult", field = "payload" }]
+            "#,
+        );
+        let profile = config
+            .profiles(&[], &ProfileConfig::default())
+            .unwrap()
+            .remove(0);
+
+        assert_eq!(
+            profile.resource_types,
+            ["Ctx", "ModelManager", "State", "Session"]
+        );
+        assert_eq!(
+            profile.envelopes,
+            [
+                Envelope::Transparent("Paged".to_owned()),
+                Envelope::Field {
+                    name: "DataRpcResult".to_owned(),
+                    field: "payload".to_owned(),
+                },
+                Envelope::Field {
+                    name: "DataRpcResult".to_owned(),
+                    field: "data".to_owned(),
+                },
+                Envelope::Transparent("Json".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let error = |toml: &str| Config::parse(toml).unwrap_err();
+
+        assert!(error(r#"rpc_file = ["**/*_rpc.rs"]"#).contains("unknown field `rpc_file`"));
+        assert!(
+            error("[profiles.web]\nclinet_dir = \"web\"").contains("unknown field `clinet_dir`")
+        );
+        assert!(Config::parse(r#"rpc_files = ["**/*_rpc.rs"]"#).is_ok());
+    }
+}
@@ -1,50 +1,214 @@
-use std::{
-    fs::{self, DirEntry},
-    path::PathBuf,
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::{
+    overrides::{Override, OverrideBuilder},
+    WalkBuilder,
 };
 
 use crate::{Error, Result};
-use std::str::FromStr;
+
+/// Directories never worth scanning, skipped on top of the configured excludes.
+const DEFAULT_EXCLUDES: &[&str] = &["target", "node_modules"];
 
 #[derive(Debug)]
 pub struct Directory {
-    pub path: PathBuf,
-    pub directories: Vec<Directory>,
+    /// Every file below the starting dir that wasn't excluded, sorted by path.
     pub files: Vec<PathBuf>,
+    /// The starting dir and every directory below it that was descended into, sorted by
+    /// path, the ones to watch for changes to the files.
+    pub dirs: Vec<PathBuf>,
 }
 
 impl Directory {
-    pub fn new(starting_dir: &str) -> Result<Self> {
-        create_dirs(Self {
-            path: PathBuf::from_str(starting_dir)
-                .map_err(|_| Error::InvalidPath(starting_dir.to_owned()))?,
-            directories: vec![],
-            files: vec![],
-        })
+    /// Walks `starting_dir`, honouring `.gitignore` and `.ignore` files and skipping hidden
+    /// files. Directories matching an `exclude` glob aren't descended into, and when there
+    /// are `include` globs only the files matching one of them are kept. Globs are matched
+    /// against paths relative to `starting_dir`, with gitignore syntax for the excludes.
+    pub fn new(starting_dir: &Path, include: &[String], exclude: &[String]) -> Result<Self> {
+        if !starting_dir.is_dir() {
+            return Err(Error::InvalidPath(starting_dir.display().to_string()));
+        }
+
+        let include = include_set(include)?;
+
+        let walker = WalkBuilder::new(starting_dir)
+            .overrides(exclude_overrides(starting_dir, exclude)?)
+            .require_git(false)
+            .follow_links(true)
+            .sort_by_file_path(|a, b| a.cmp(b))
+            .build();
+
+        let mut files = vec![];
+        let mut dirs = vec![];
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // Symlink loops and unreadable directories end up here.
+                Err(e) => {
+                    eprintln!("WARNING: Skipping {e}");
+                    continue;
+                }
+            };
+            if entry.file_type().is_some_and(|t| t.is_dir()) {
+                dirs.push(entry.into_path());
+                continue;
+            }
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let relative = entry
+                .path()
+                .strip_prefix(starting_dir)
+                .unwrap_or(entry.path());
+            if include.as_ref().is_some_and(|set| !set.is_match(relative)) {
+                continue;
+            }
+            files.push(entry.into_path());
+        }
+
+        Ok(Directory { files, dirs })
     }
 }
 
-pub fn get_dir_content<'a>(path: PathBuf) -> Result<Vec<DirEntry>> {
-    Ok(fs::read_dir(path)?
-        .filter_map(|result| result.ok())
-        .collect())
+fn exclude_overrides(root: &Path, exclude: &[String]) -> Result<Override> {
+    let mut overrides = OverrideBuilder::new(root);
+    for glob in DEFAULT_EXCLUDES
+        .iter()
+        .copied()
+        .chain(exclude.iter().map(String::as_str))
+    {
+        overrides
+            .add(&format!("!{glob}"))
+            .map_err(|e| Error::InvalidConfig(format!("exclude {glob}: {e}")))?;
+    }
+
+    overrides
+        .build()
+        .map_err(|e| Error::InvalidConfig(e.to_string()))
 }
 
-pub fn create_dirs(mut directory: Directory) -> Result<Directory> {
-    let dirs = get_dir_content(directory.path.clone())?;
+fn include_set(include: &[String]) -> Result<Option<GlobSet>> {
+    if include.is_empty() {
+        return Ok(None);
+    }
 
-    for dir in dirs.iter() {
-        if dir.path().is_dir() {
-            let this_dir = Directory {
-                path: dir.path(),
-                directories: vec![],
-                files: vec![],
-            };
-            directory.directories.push(create_dirs(this_dir)?)
-        } else if dir.path().is_file() {
-            directory.files.push(dir.path())
+    let mut builder = GlobSetBuilder::new();
+    for glob in include {
+        builder.add(
+            Glob::new(glob).map_err(|e| Error::InvalidConfig(format!("include {glob}: {e}")))?,
+        );
+    }
+    let set = builder
+        .build()
+        .map_err(|e| Error::InvalidConfig(e.to_string()))?;
+
+    Ok(Some(set))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("client_gen_walk_{name}"));
+        let _ = fs::remove_dir_all(&root);
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
         }
+        root
+    }
+
+    /// The files and directories of the walk, relative to `root`.
+    fn walk(root: &Path, include: &[&str], exclude: &[&str]) -> (Vec<String>, Vec<String>) {
+        let globs = |globs: &[&str]| globs.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        let directory = Directory::new(root, &globs(include), &globs(exclude)).unwrap();
+        let relative = |paths: Vec<PathBuf>| {
+            paths
+                .iter()
+                .map(|path| path.strip_prefix(root).unwrap().display().to_string())
+                .collect()
+        };
+        (relative(directory.files), relative(directory.dirs))
+    }
+
+    #[test]
+    fn honours_ignore_files_and_skips_hidden_ones() {
+        let root = fixture(
+            "ignore_files",
+            &[
+                (".gitignore", "generated/\n"),
+                ("src/.ignore", "scratch.rs\n"),
+                ("src/.hidden.rs", ""),
+                ("src/lib.rs", ""),
+                ("src/scratch.rs", ""),
+                ("generated/bindings.ts", ""),
+            ],
+        );
+
+        let (files, dirs) = walk(&root, &[], &[]);
+        assert_eq!(files, ["src/lib.rs"]);
+        assert_eq!(dirs, ["", "src"]);
+    }
+
+    #[test]
+    fn skips_target_and_node_modules() {
+        let root = fixture(
+            "default_excludes",
+            &[
+                ("src/lib.rs", ""),
+                ("target/debug/build.rs", ""),
+                ("web/node_modules/pkg/index.rs", ""),
+                ("web/bindings.ts", ""),
+            ],
+        );
+
+        let (files, dirs) = walk(&root, &[], &[]);
+        assert_eq!(files, ["src/lib.rs", "web/bindings.ts"]);
+        assert_eq!(dirs, ["", "src", "web"]);
     }
 
-    Ok(directory)
+    #[test]
+    fn prunes_excluded_dirs_and_keeps_included_files() {
+        let root = fixture(
+            "include_exclude",
+            &[
+                ("src/lib.rs", ""),
+                ("src/rpc/task_rpc.rs", ""),
+                ("src/rpc/README.md", ""),
+                ("examples/demo/main.rs", ""),
+            ],
+        );
+
+        let (files, dirs) = walk(&root, &["src/**/*.rs"], &["examples"]);
+        assert_eq!(files, ["src/lib.rs", "src/rpc/task_rpc.rs"]);
+        // Excluded dirs aren't descended into, includes only filter the files.
+        assert_eq!(dirs, ["", "src", "src/rpc"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn survives_symlink_loops() {
+        let root = fixture("symlink_loop", &[("src/lib.rs", "")]);
+        std::os::unix::fs::symlink(&root, root.join("src/root")).unwrap();
+
+        let (files, dirs) = walk(&root, &[], &[]);
+        assert_eq!(files, ["src/lib.rs"]);
+        assert_eq!(dirs, ["", "src"]);
+    }
+
+    #[test]
+    fn rejects_missing_dirs() {
+        let root = std::env::temp_dir().join("client_gen_walk_missing");
+        let _ = fs::remove_dir_all(&root);
+
+        assert!(matches!(
+            Directory::new(&root, &[], &[]),
+            Err(Error::InvalidPath(_))
+        ));
+    }
 }
@@ -5,15 +5,22 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug, From)]
 pub enum Error {
     InvalidPath(String),
+    InvalidConfig(String),
+    RustParse(String),
+    Serialize(String),
+    UnknownProfile(String),
+    InvalidClientName(String),
 
     UnknownCommonRpcFnsEntry(String),
     EntityMissingFromRpcFns(String),
-    SuffixMissingFromRpcFns,
+    SuffixMissingFromRpcFns(String),
     ForCreateMissingFromRpcFns,
     ForUpdateMissingFromRpcFns,
     FilterMissingFromRpcFns,
     CantMatchHandlerReturnType(String),
     CantMatchHandlerParams(String),
+    TypeshareFailed(std::process::ExitStatus),
+    Watch(String),
 
     #[from]
     Io(std::io::Error),
@@ -0,0 +1,173 @@
+use std::{fs, io, process::Command};
+
+use similar::TextDiff;
+
+use crate::{
+    backend::{Backend, JsonSchema, OpenRpc, TypeScript},
+    config::{GeneratorConfig, MethodOrder, OutputFormat},
+    Error, Result, RpcModel, Scanner,
+};
+
+/// Scans a backend and writes the client described by a [`GeneratorConfig`].
+#[derive(Debug, Clone)]
+pub struct Generator {
+    config: GeneratorConfig,
+}
+
+impl Generator {
+    /// Generator of the client `config` describes. Nothing is read until it is used.
+    pub fn new(config: GeneratorConfig) -> Self {
+        Generator { config }
+    }
+
+    /// The config the generator was created with.
+    pub fn config(&self) -> &GeneratorConfig {
+        &self.config
+    }
+
+    /// Scans `config.root` and writes the client to `config.output_path()`.
+    pub fn generate(&self) -> Result<RpcModel> {
+        let model = self.scan()?;
+        self.write(&model)?;
+
+        Ok(model)
+    }
+
+    /// Scans `config.root`, through the cache in `config.cache_dir` if there is one.
+    pub fn scan(&self) -> Result<RpcModel> {
+        let scanner = Scanner::new(&self.config.root)
+            .with_include(self.config.include.clone())
+            .with_exclude(self.config.exclude.clone())
+            .with_rpc_files(self.config.rpc_files.clone())
+            .with_entity_pattern(&self.config.entity_pattern)
+            .with_resource_types(self.config.resource_types.clone());
+
+        match &self.config.cache_dir {
+            Some(dir) => scanner.with_cache(dir).scan(),
+            None => scanner.scan(),
+        }
+    }
+
+    /// Renders `model` in `config.format` and writes it to `config.output_path()`, creating
+    /// `config.client_dir` if needed.
+    pub fn write(&self, model: &RpcModel) -> Result<()> {
+        fs::create_dir_all(&self.config.client_dir)?;
+        fs::write(self.config.output_path(), self.render(model)?)?;
+
+        Ok(())
+    }
+
+    /// Unified diff from the file at `config.output_path()` to what `model` renders into, or
+    /// `None` when the file is up to date. A missing file diffs as empty.
+    pub fn diff(&self, model: &RpcModel) -> Result<Option<String>> {
+        let path = self.config.output_path();
+        let rendered = self.render(model)?;
+        let existing = match fs::read_to_string(&path) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        if existing == rendered {
+            return Ok(None);
+        }
+
+        let path = path.display().to_string();
+        let diff = TextDiff::from_lines(&existing, &rendered)
+            .unified_diff()
+            .header(&path, &format!("{path} (generated)"))
+            .to_string();
+
+        Ok(Some(diff))
+    }
+
+    /// Renders `model` in the configured format without touching the filesystem.
+    pub fn render(&self, model: &RpcModel) -> Result<String> {
+        match self.config.format {
+            OutputFormat::TypeScript => self.render_with(&TypeScript, model),
+            OutputFormat::OpenRpc => self.render_with(&OpenRpc, model),
+            OutputFormat::JsonSchema => self.render_with(&JsonSchema, model),
+        }
+    }
+
+    /// Renders `model` with `backend`, methods ordered as configured.
+    pub fn render_with(&self, backend: &dyn Backend, model: &RpcModel) -> Result<String> {
+        match self.config.method_order {
+            MethodOrder::Source => backend.render(model, &self.config),
+            MethodOrder::Alphabetical => {
+                let mut model = model.clone();
+                model.sort_methods();
+                backend.render(&model, &self.config)
+            }
+        }
+    }
+
+    /// Runs `typeshare` over `config.root`, writing `bindings.ts` into `config.types_dir`.
+    pub fn run_typeshare(&self) -> Result<()> {
+        let status = Command::new("typeshare")
+            .arg("--lang")
+            .arg("typescript")
+            .arg("--output-file")
+            .arg(self.config.types_dir.join("bindings.ts"))
+            .arg(&self.config.root)
+            .status()?;
+
+        if !status.success() {
+            return Err(Error::TypeshareFailed(status));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Generator writing into a directory of its own for each test.
+    fn generator(name: &str) -> Generator {
+        let root = std::env::temp_dir().join(format!("client_gen_diff_{name}"));
+        let _ = fs::remove_dir_all(&root);
+        Generator::new(GeneratorConfig {
+            client_dir: root.join("client"),
+            types_dir: root.join("types"),
+            ..GeneratorConfig::new(root)
+        })
+    }
+
+    #[test]
+    fn missing_client_diffs_as_empty() {
+        let generator = generator("missing");
+        let diff = generator.diff(&RpcModel::default()).unwrap().unwrap();
+
+        let path = generator.config().output_path().display().to_string();
+        assert!(diff.starts_with(&format!("--- {path}\n+++ {path} (generated)\n")));
+        assert!(diff.lines().skip(3).all(|line| !line.starts_with('-')));
+    }
+
+    #[test]
+    fn written_client_is_up_to_date() {
+        let generator = generator("up_to_date");
+        let model = RpcModel::default();
+        generator.write(&model).unwrap();
+
+        assert_eq!(generator.diff(&model).unwrap(), None);
+    }
+
+    #[test]
+    fn edited_client_is_stale() {
+        let generator = generator("stale");
+        let model = RpcModel::default();
+        generator.write(&model).unwrap();
+        let path: PathBuf = generator.config().output_path();
+        let edited = fs::read_to_string(&path).unwrap() + "export const edited = true;\n";
+        fs::write(&path, edited).unwrap();
+
+        let diff = generator.diff(&model).unwrap().unwrap();
+        assert!(diff
+            .lines()
+            .any(|line| line.starts_with('-') && line.contains("export const edited")));
+    }
+}
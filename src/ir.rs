@@ -0,0 +1,295 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The RPC surface of a backend, as found by the [`Scanner`](crate::Scanner). Backends
+/// render it into output files, and nothing in it is specific to one output language.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcModel {
+    /// Type names exported by the typeshare `bindings.ts` files.
+    pub bindings: BTreeSet<String>,
+    /// Services sorted by name, their methods in source order.
+    pub services: Vec<Service>,
+    /// Definitions of the types used by the methods, directly or through other types.
+    pub types: BTreeMap<String, TypeDef>,
+}
+
+impl RpcModel {
+    /// Adds `service`, merging it into an existing service of the same name.
+    pub fn add_service(&mut self, service: Service) {
+        match self
+            .services
+            .binary_search_by(|s| s.name.as_str().cmp(&service.name))
+        {
+            Ok(i) => self.services[i].methods.extend(service.methods),
+            Err(i) => self.services.insert(i, service),
+        }
+    }
+
+    /// Sorts the methods of every service by name, keeping source order between methods
+    /// of the same name.
+    pub fn sort_methods(&mut self) {
+        for service in self.services.iter_mut() {
+            service.methods.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+
+    pub fn methods(&self) -> impl Iterator<Item = (&Service, &Method)> {
+        self.services
+            .iter()
+            .flat_map(|service| service.methods.iter().map(move |method| (service, method)))
+    }
+}
+
+/// The handlers of one entity's RPC module, rendered as one client object.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Service {
+    /// Entity name, e.g. `patient` for `patient_rpc.rs`.
+    pub name: String,
+    pub methods: Vec<Method>,
+}
+
+/// A single handler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Method {
+    /// Name of the method on the client object.
+    pub name: String,
+    /// Name the handler is called by over JSON-RPC.
+    pub rpc_name: String,
+    /// Client-facing parameters, with server-side resources like `Ctx` left out.
+    pub params: Vec<Param>,
+    /// Return type, e.g. `Result<DataRpcResult<Task>>`. `()` when the handler has none.
+    pub result: RustType,
+    /// Lines of the handler's doc comment.
+    pub docs: Vec<String>,
+    pub location: SourceLocation,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Param {
+    pub name: String,
+    pub ty: RustType,
+}
+
+/// A Rust type, reduced to what backends need to translate it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RustType {
+    /// `Vec<Task>`, `uuid::Uuid`: the path's segments, and the generic arguments of the last one.
+    Path {
+        segments: Vec<String>,
+        args: Vec<RustType>,
+    },
+    /// `(A, B)`, with `()` as the empty tuple.
+    Tuple(Vec<RustType>),
+    /// `[T; N]` and `[T]`.
+    Array(Box<RustType>),
+    Reference(Box<RustType>),
+    /// Anything else (`impl Trait`, function pointers, ...), as written in the source.
+    Other(String),
+}
+
+impl RustType {
+    pub fn unit() -> Self {
+        RustType::Tuple(vec![])
+    }
+
+    /// Last path segment, e.g. `Uuid` for `uuid::Uuid`.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            RustType::Path { segments, .. } => segments.last().map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Full path without generics, e.g. `uuid::Uuid`.
+    pub fn path(&self) -> Option<String> {
+        match self {
+            RustType::Path { segments, .. } => Some(segments.join("::")),
+            _ => None,
+        }
+    }
+
+    pub fn args(&self) -> &[RustType] {
+        match self {
+            RustType::Path { args, .. } => args,
+            _ => &[],
+        }
+    }
+
+    /// Replaces generic parameters, e.g. `D` in `Vec<D>` with `generics["D"]`.
+    pub fn substitute(&self, generics: &BTreeMap<String, RustType>) -> RustType {
+        match self {
+            RustType::Path { segments, args } => match (segments.as_slice(), args.is_empty()) {
+                ([name], true) if generics.contains_key(name) => generics[name].clone(),
+                _ => RustType::Path {
+                    segments: segments.clone(),
+                    args: args.iter().map(|arg| arg.substitute(generics)).collect(),
+                },
+            },
+            RustType::Tuple(types) => {
+                RustType::Tuple(types.iter().map(|ty| ty.substitute(generics)).collect())
+            }
+            RustType::Array(inner) => RustType::Array(Box::new(inner.substitute(generics))),
+            RustType::Reference(inner) => RustType::Reference(Box::new(inner.substitute(generics))),
+            RustType::Other(ty) => RustType::Other(ty.clone()),
+        }
+    }
+
+    /// Names of every path type in `self`, including generic arguments.
+    pub fn names(&self) -> BTreeSet<&str> {
+        let mut names = BTreeSet::new();
+        self.collect_names(&mut names);
+        names
+    }
+
+    fn collect_names<'a>(&'a self, names: &mut BTreeSet<&'a str>) {
+        match self {
+            RustType::Path { args, .. } => {
+                names.extend(self.name());
+                args.iter().for_each(|arg| arg.collect_names(names));
+            }
+            RustType::Tuple(types) => types.iter().for_each(|ty| ty.collect_names(names)),
+            RustType::Array(inner) | RustType::Reference(inner) => inner.collect_names(names),
+            RustType::Other(_) => {}
+        }
+    }
+}
+
+/// A struct or enum definition, with names as serde serializes them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeDef {
+    pub name: String,
+    /// Names of the type parameters, e.g. `["D"]` for `ParamsForCreate<D>`.
+    pub generics: Vec<String>,
+    pub kind: TypeDefKind,
+    pub docs: Vec<String>,
+    pub location: SourceLocation,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TypeDefKind {
+    Struct(Fields),
+    Enum {
+        variants: Vec<Variant>,
+        tagging: EnumTagging,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Fields {
+    Named(Vec<Field>),
+    /// A single unnamed field, serialized as the field itself.
+    Newtype(RustType),
+    Tuple(Vec<RustType>),
+    Unit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub ty: RustType,
+    /// `false` for `Option`s and `#[serde(default)]` fields.
+    pub required: bool,
+    /// `#[serde(flatten)]`
+    pub flatten: bool,
+    pub docs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Fields,
+    pub docs: Vec<String>,
+}
+
+/// How serde represents the variants of an enum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnumTagging {
+    External,
+    Internal { tag: String },
+    Adjacent { tag: String, content: String },
+    Untagged,
+}
+
+impl TypeDef {
+    /// Types referenced by the fields of `self`.
+    pub fn field_types(&self) -> Vec<&RustType> {
+        match &self.kind {
+            TypeDefKind::Struct(fields) => fields.types(),
+            TypeDefKind::Enum { variants, .. } => variants
+                .iter()
+                .flat_map(|variant| variant.fields.types())
+                .collect(),
+        }
+    }
+}
+
+impl Fields {
+    pub fn types(&self) -> Vec<&RustType> {
+        match self {
+            Fields::Named(fields) => fields.iter().map(|f| &f.ty).collect(),
+            Fields::Newtype(ty) => vec![ty],
+            Fields::Tuple(types) => types.iter().collect(),
+            Fields::Unit => vec![],
+        }
+    }
+}
+
+/// Where a method is defined, or the macro invocation that generates it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|p| format!("{}: {}", p.name, p.ty))
+            .collect::<Vec<String>>()
+            .join(", ");
+        write!(f, "{}({params}) -> {}", self.name, self.result)?;
+        if self.rpc_name != self.name {
+            write!(f, " as \"{}\"", self.rpc_name)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RustType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |types: &[RustType]| {
+            types
+                .iter()
+                .map(RustType::to_string)
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        match self {
+            RustType::Path { segments, args } if args.is_empty() => {
+                write!(f, "{}", segments.join("::"))
+            }
+            RustType::Path { segments, args } => {
+                write!(f, "{}<{}>", segments.join("::"), join(args))
+            }
+            RustType::Tuple(types) if types.len() == 1 => write!(f, "({},)", types[0]),
+            RustType::Tuple(types) => write!(f, "({})", join(types)),
+            RustType::Array(inner) => write!(f, "[{inner}]"),
+            RustType::Reference(inner) => write!(f, "&{inner}"),
+            RustType::Other(ty) => write!(f, "{ty}"),
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
@@ -0,0 +1,50 @@
+//! Generates a TypeScript client for the [rpc-router] handlers of a Rust backend.
+//!
+//! The `client_gen` binary wraps this crate, but it can also be driven from a `build.rs`
+//! or an xtask so the client is regenerated whenever the RPC crate compiles:
+//!
+//! ```no_run
+//! use client_gen::{Generator, GeneratorConfig};
+//!
+//! fn main() -> client_gen::Result<()> {
+//!     let config = GeneratorConfig::new("..");
+//!     Generator::new(config).generate()?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Handlers outside the rpc files are picked up when marked with [`rpc`], which leaves them
+//! as they are. Backends that would rather not depend on this crate can use the attribute
+//! from `client_gen_macros`, or define their own: any attribute whose path ends in `rpc`
+//! marks a handler, but only this one checks its options when compiling.
+//!
+//! [rpc-router]: https://crates.io/crates/rpc-router
+
+mod backend;
+mod cache;
+mod config;
+mod directories;
+mod error;
+mod generator;
+mod ir;
+mod parser;
+mod process_rpc;
+mod scanner;
+mod util;
+mod watch;
+
+pub use client_gen_macros::rpc;
+
+pub use backend::{Backend, JsonSchema, JsonSchemaMapper, OpenRpc, TsTypeMapper, TypeScript};
+pub use config::{
+    Config, Envelope, GeneratorConfig, MethodOrder, OutputFormat, ParamStructure, ProfileConfig,
+    TypeImport, TypeMapping, CONFIG_FILE_NAME,
+};
+pub use error::{Error, Result};
+pub use generator::Generator;
+pub use ir::{
+    EnumTagging, Field, Fields, Method, Param, RpcModel, RustType, Service, SourceLocation,
+    TypeDef, TypeDefKind, Variant,
+};
+pub use scanner::Scanner;
+pub use watch::watch;
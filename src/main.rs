@@ -1,46 +1,94 @@
-mod directories;
-mod error;
-mod process_rpc;
-mod util;
+mod cli;
 
-pub use error::{Error, Result};
+use clap::Parser;
+use std::process::ExitCode;
 
-use directories::Directory;
-use process_rpc::OutputFileContent;
+use cli::{Cli, Command, DirArgs, GenerateArgs};
+use client_gen::{Config, Generator, GeneratorConfig, OutputFormat, Result};
 
-static ROOT_DIR: &str = "/home/eran/code/early_medical";
-static TYPES_DIR: &str = "/home/eran/code/early_medical/frontend/src/lib/types";
-static CLIENT_DIR: &str = "/home/eran/code/early_medical/frontend/src/lib/api/client";
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
 
-fn main() -> Result<()> {
-    run_typeshare();
-    println!("Generating client!");
-    let starting_dir = Directory::new(ROOT_DIR)?;
-
-    let output_file_content = OutputFileContent::new(&starting_dir)?;
+    match cli.command {
+        Command::Check(args)
+        | Command::Generate(GenerateArgs {
+            dirs: args,
+            check: true,
+            ..
+        }) => {
+            return check(&args);
+        }
+        Command::Generate(GenerateArgs {
+            dirs: args,
+            skip_typeshare,
+            ..
+        }) => {
+            for config in configs(&args)? {
+                let generator = Generator::new(config);
+                if !skip_typeshare && generator.config().format == OutputFormat::TypeScript {
+                    println!("Running typeshare!");
+                    generator.run_typeshare()?;
+                }
+                println!(
+                    "Generating {} for profile {}!",
+                    generator.config().output_file.display(),
+                    generator.config().name
+                );
+                generator.generate()?;
+            }
+        }
+        Command::Watch(args) => {
+            let generators: Vec<Generator> =
+                configs(&args)?.into_iter().map(Generator::new).collect();
+            client_gen::watch(&generators)?;
+        }
+        Command::ListHandlers(args) => {
+            for config in configs(&args)? {
+                println!("[{}]", config.name);
+                let model = Generator::new(config.clone()).scan()?;
+                for service in model.services.iter() {
+                    println!("{}:", service.name);
+                    for method in service.methods.iter() {
+                        println!("    {method}    ({})", method.location);
+                    }
+                }
+            }
+        }
+    }
 
-    // println!("{output_file_content:#?}");
+    Ok(ExitCode::SUCCESS)
+}
 
-    output_file_content.write_to_file(CLIENT_DIR, TYPES_DIR);
+/// Compares the client of every profile with the written one, failing when any is stale.
+fn check(args: &DirArgs) -> Result<ExitCode> {
+    let mut stale = false;
+    for config in configs(args)? {
+        let generator = Generator::new(config);
+        let model = generator.scan()?;
+        match generator.diff(&model)? {
+            Some(diff) => {
+                stale = true;
+                print!("{diff}");
+                println!(
+                    "{} is out of date for profile {}, run `client_gen generate`",
+                    generator.config().output_path().display(),
+                    generator.config().name
+                );
+            }
+            None => println!(
+                "{} is up to date for profile {}",
+                generator.config().output_path().display(),
+                generator.config().name
+            ),
+        }
+    }
 
-    Ok(())
-}
-use std::process::Command;
-
-fn run_typeshare() {
-    println!("Running typeshare!");
-    let status = Command::new("typeshare")
-        .args(&[
-            "--lang",
-            "typescript",
-            "--output-file",
-            format!("{TYPES_DIR}/bindings.ts").as_str(),
-            format!("{ROOT_DIR}").as_str(),
-        ])
-        .status()
-        .expect("Failed to execute command");
-
-    if !status.success() {
-        panic!("Command executed with failing error code");
+    match stale {
+        true => Ok(ExitCode::FAILURE),
+        false => Ok(ExitCode::SUCCESS),
     }
 }
+
+fn configs(args: &DirArgs) -> Result<Vec<GeneratorConfig>> {
+    Config::load(args.config.as_deref())?.profiles(&args.profiles, &args.overrides()?)
+}
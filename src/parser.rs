@@ -0,0 +1,1133 @@
+use std::{collections::HashMap, path::Path};
+
+use quote::ToTokens;
+use syn::{
+    parse::Parser,
+    punctuated::Punctuated,
+    visit::{self, Visit},
+    Expr, FnArg, GenericArgument, Ident, Item, PathArguments, ReturnType, Token, Type,
+};
+
+use crate::{
+    ir::{EnumTagging, Field, Fields, RustType, SourceLocation, TypeDef, TypeDefKind, Variant},
+    util::camel_to_snake,
+    Error, Result,
+};
+
+/// An `async fn` found at module level, anywhere in the file.
+#[derive(Debug, Clone)]
+pub struct HandlerFn {
+    pub name: String,
+    pub params: Vec<FnParam>,
+    /// `None` when the function has no `-> ...`.
+    pub output: Option<Type>,
+    pub docs: Vec<String>,
+    pub line: usize,
+    pub marker: Option<RpcMarker>,
+}
+
+/// Options of a function marked as a handler with `#[rpc(...)]`, under any path ending in
+/// `rpc` such as `#[client_gen::rpc]`, or with a `/// @rpc ...` doc line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RpcMarker {
+    /// Name of the method on the client object, `name = "..."`.
+    pub name: Option<String>,
+    /// Client object the method goes into instead of the file's, `client = "..."`.
+    pub client: Option<String>,
+    /// JSON-RPC method the handler is registered under, `method = "..."`.
+    pub method: Option<String>,
+    /// `skip`, leaves the handler out even when it is registered with the router.
+    pub skip: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct FnParam {
+    pub pat: syn::Pat,
+    pub ty: Type,
+}
+
+/// One `Key: Type` entry of `generate_common_rpc_fns!`.
+#[derive(Debug, Clone)]
+pub struct MacroKeyValue {
+    pub key: Ident,
+    pub value: Type,
+}
+
+impl syn::parse::Parse for MacroKeyValue {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let key = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let value = input.parse()?;
+        Ok(MacroKeyValue { key, value })
+    }
+}
+
+/// Parses `content`, the source of the file at `path`.
+pub fn parse_file(path: &Path, content: &str) -> Result<syn::File> {
+    syn::parse_file(content).map_err(|e| Error::RustParse(format!("{}: {e}", path.display())))
+}
+
+pub fn handler_fns(file: &syn::File) -> Vec<HandlerFn> {
+    let mut handlers = vec![];
+    collect_handler_fns(&file.items, &mut handlers);
+    handlers
+}
+
+fn collect_handler_fns(items: &[Item], handlers: &mut Vec<HandlerFn>) {
+    for item in items {
+        match item {
+            Item::Fn(item_fn) if item_fn.sig.asyncness.is_some() => {
+                let params = item_fn
+                    .sig
+                    .inputs
+                    .iter()
+                    .filter_map(|input| match input {
+                        FnArg::Typed(pat_type) => Some(FnParam {
+                            pat: (*pat_type.pat).clone(),
+                            ty: (*pat_type.ty).clone(),
+                        }),
+                        FnArg::Receiver(_) => None,
+                    })
+                    .collect();
+                let output = match &item_fn.sig.output {
+                    ReturnType::Default => None,
+                    ReturnType::Type(_, ty) => Some((**ty).clone()),
+                };
+                handlers.push(HandlerFn {
+                    name: item_fn.sig.ident.to_string(),
+                    params,
+                    output,
+                    docs: doc_lines(&item_fn.attrs)
+                        .into_iter()
+                        .filter(|line| doc_marker(line).is_none())
+                        .collect(),
+                    line: item_fn.sig.ident.span().start().line,
+                    marker: rpc_marker(&item_fn.attrs),
+                });
+            }
+            Item::Mod(item_mod) => {
+                if let Some((_, items)) = &item_mod.content {
+                    collect_handler_fns(items, handlers);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn rpc_marker(attrs: &[syn::Attribute]) -> Option<RpcMarker> {
+    let attr_marker = attrs
+        .iter()
+        .find(|attr| {
+            attr.path()
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "rpc")
+        })
+        .map(|attr| {
+            let mut marker = RpcMarker::default();
+            if let syn::Meta::List(_) = attr.meta {
+                let parsed = attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("name") {
+                        marker.name = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                    } else if meta.path.is_ident("client") {
+                        marker.client = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                    } else if meta.path.is_ident("method") {
+                        marker.method = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                    } else if meta.path.is_ident("skip") {
+                        marker.skip = true;
+                    } else {
+                        eprintln!(
+                            "WARNING: Ignoring unknown #[rpc] option {}",
+                            tokens_to_string(&meta.path)
+                        );
+                        if meta.input.peek(Token![=]) {
+                            meta.value()?.parse::<Expr>()?;
+                        }
+                    }
+                    Ok(())
+                });
+                if let Err(e) = parsed {
+                    eprintln!(
+                        "WARNING: Ignoring the #[rpc] options from line {} on: {e}",
+                        e.span().start().line
+                    );
+                }
+            }
+            marker
+        });
+
+    attr_marker.or_else(|| doc_lines(attrs).iter().find_map(|line| doc_marker(line)))
+}
+
+/// `@rpc name=list_all client=patient method=patients.list skip`, any of the options being
+/// optional.
+fn doc_marker(line: &str) -> Option<RpcMarker> {
+    let options = line.strip_prefix("@rpc")?;
+    if !options.is_empty() && !options.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let mut marker = RpcMarker::default();
+    for option in options.split_whitespace() {
+        let value = |value: &str| value.trim_matches('"').to_owned();
+        match option.split_once('=') {
+            Some(("name", name)) => marker.name = Some(value(name)),
+            Some(("client", client)) => marker.client = Some(value(client)),
+            Some(("method", method)) => marker.method = Some(value(method)),
+            None if option == "skip" => marker.skip = true,
+            _ => eprintln!("WARNING: Ignoring unknown @rpc option {option}"),
+        }
+    }
+
+    Some(marker)
+}
+
+/// Text of the `///` comments among `attrs`, one entry per line.
+pub fn doc_lines(attrs: &[syn::Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(doc),
+                        ..
+                    }),
+                ..
+            }) => Some(doc.value().trim().to_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Struct and enum definitions anywhere in the file, with serde's renames applied.
+pub fn type_defs(file: &syn::File, path: &Path) -> Vec<TypeDef> {
+    let mut defs = vec![];
+    collect_type_defs(&file.items, path, &mut defs);
+    defs
+}
+
+fn collect_type_defs(items: &[Item], path: &Path, defs: &mut Vec<TypeDef>) {
+    for item in items {
+        match item {
+            Item::Struct(item_struct) => {
+                let serde = SerdeAttrs::from_attrs(&item_struct.attrs);
+                let mut fields = fields(&item_struct.fields, &serde);
+                if serde.transparent {
+                    if let Fields::Named(named) = &fields {
+                        if let Some(field) = named.first() {
+                            fields = Fields::Newtype(field.ty.clone());
+                        }
+                    }
+                }
+                defs.push(TypeDef {
+                    name: item_struct.ident.to_string(),
+                    generics: type_params(&item_struct.generics),
+                    kind: TypeDefKind::Struct(fields),
+                    docs: doc_lines(&item_struct.attrs),
+                    location: SourceLocation {
+                        file: path.to_path_buf(),
+                        line: item_struct.ident.span().start().line,
+                    },
+                });
+            }
+            Item::Enum(item_enum) => {
+                let serde = SerdeAttrs::from_attrs(&item_enum.attrs);
+                let variants = item_enum
+                    .variants
+                    .iter()
+                    .filter_map(|variant| {
+                        let variant_serde = SerdeAttrs::from_attrs(&variant.attrs);
+                        if variant_serde.skip {
+                            return None;
+                        }
+                        let name = variant_serde.rename.clone().unwrap_or_else(|| {
+                            rename(
+                                &variant.ident.to_string(),
+                                serde.rename_all.as_deref(),
+                                true,
+                            )
+                        });
+                        Some(Variant {
+                            name,
+                            fields: fields(&variant.fields, &variant_serde),
+                            docs: doc_lines(&variant.attrs),
+                        })
+                    })
+                    .collect();
+                let tagging = match (serde.untagged, serde.tag, serde.content) {
+                    (true, _, _) => EnumTagging::Untagged,
+                    (false, Some(tag), Some(content)) => EnumTagging::Adjacent { tag, content },
+                    (false, Some(tag), None) => EnumTagging::Internal { tag },
+                    (false, None, _) => EnumTagging::External,
+                };
+                defs.push(TypeDef {
+                    name: item_enum.ident.to_string(),
+                    generics: type_params(&item_enum.generics),
+                    kind: TypeDefKind::Enum { variants, tagging },
+                    docs: doc_lines(&item_enum.attrs),
+                    location: SourceLocation {
+                        file: path.to_path_buf(),
+                        line: item_enum.ident.span().start().line,
+                    },
+                });
+            }
+            Item::Mod(item_mod) => {
+                if let Some((_, items)) = &item_mod.content {
+                    collect_type_defs(items, path, defs);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn type_params(generics: &syn::Generics) -> Vec<String> {
+    generics
+        .type_params()
+        .map(|param| param.ident.to_string())
+        .collect()
+}
+
+/// `container` holds the serde attributes of the struct or variant owning the fields.
+fn fields(fields: &syn::Fields, container: &SerdeAttrs) -> Fields {
+    match fields {
+        syn::Fields::Named(named) => Fields::Named(
+            named
+                .named
+                .iter()
+                .filter_map(|field| {
+                    let serde = SerdeAttrs::from_attrs(&field.attrs);
+                    if serde.skip {
+                        return None;
+                    }
+                    let ident = field.ident.as_ref()?.to_string();
+                    let ident = ident.trim_start_matches("r#");
+                    let ty = rust_type(&field.ty);
+                    Some(Field {
+                        name: serde.rename.unwrap_or_else(|| {
+                            rename(ident, container.rename_all.as_deref(), false)
+                        }),
+                        required: !(ty.name() == Some("Option")
+                            || serde.default
+                            || serde.skip_if
+                            || container.default),
+                        flatten: serde.flatten,
+                        docs: doc_lines(&field.attrs),
+                        ty,
+                    })
+                })
+                .collect(),
+        ),
+        syn::Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+            Fields::Newtype(rust_type(&unnamed.unnamed[0].ty))
+        }
+        syn::Fields::Unnamed(unnamed) => {
+            Fields::Tuple(unnamed.unnamed.iter().map(|f| rust_type(&f.ty)).collect())
+        }
+        syn::Fields::Unit => Fields::Unit,
+    }
+}
+
+/// Applies a `#[serde(rename_all = "...")]` rule. Fields are written in snake_case and
+/// variants in PascalCase, which is what the rules convert from.
+fn rename(name: &str, rule: Option<&str>, is_variant: bool) -> String {
+    let Some(rule) = rule else {
+        return name.to_owned();
+    };
+
+    let snake = if is_variant {
+        camel_to_snake(name)
+    } else {
+        name.to_owned()
+    };
+    let words: Vec<&str> = snake.split('_').filter(|w| !w.is_empty()).collect();
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+            .unwrap_or_default()
+    };
+
+    match rule {
+        "lowercase" => words.concat().to_lowercase(),
+        "UPPERCASE" => words.concat().to_uppercase(),
+        "PascalCase" => words.iter().map(|w| capitalize(w)).collect(),
+        "camelCase" => words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| if i == 0 { w.to_string() } else { capitalize(w) })
+            .collect(),
+        "snake_case" => words.join("_"),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "SCREAMING-KEBAB-CASE" => words.join("-").to_uppercase(),
+        _ => name.to_owned(),
+    }
+}
+
+/// The parts of `#[serde(...)]` that change the JSON shape of a type.
+#[derive(Debug, Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    transparent: bool,
+    skip: bool,
+    skip_if: bool,
+    default: bool,
+    flatten: bool,
+}
+
+impl SerdeAttrs {
+    fn from_attrs(attrs: &[syn::Attribute]) -> Self {
+        let mut serde = SerdeAttrs::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            // Attributes we can't make sense of are left for rustc to complain about.
+            let _ = attr.parse_nested_meta(|meta| {
+                let ident = meta
+                    .path
+                    .get_ident()
+                    .map(|i| i.to_string())
+                    .unwrap_or_default();
+                match ident.as_str() {
+                    "rename" => serde.rename = serialized_name(&meta)?,
+                    "rename_all" => serde.rename_all = serialized_name(&meta)?,
+                    "tag" => serde.tag = Some(meta.value()?.parse::<syn::LitStr>()?.value()),
+                    "content" => {
+                        serde.content = Some(meta.value()?.parse::<syn::LitStr>()?.value())
+                    }
+                    "untagged" => serde.untagged = true,
+                    "transparent" => serde.transparent = true,
+                    "skip" | "skip_serializing" | "skip_deserializing" => serde.skip = true,
+                    "flatten" => serde.flatten = true,
+                    "default" => {
+                        serde.default = true;
+                        skip_meta_value(&meta)?;
+                    }
+                    "skip_serializing_if" => {
+                        serde.skip_if = true;
+                        skip_meta_value(&meta)?;
+                    }
+                    _ => skip_meta_value(&meta)?,
+                }
+                Ok(())
+            });
+        }
+
+        serde
+    }
+}
+
+/// Value of `rename = "..."`, or of its `serialize = "..."` part in the
+/// `rename(serialize = "...", deserialize = "...")` form.
+fn serialized_name(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Option<String>> {
+    if meta.input.peek(Token![=]) {
+        return Ok(Some(meta.value()?.parse::<syn::LitStr>()?.value()));
+    }
+
+    let mut name = None;
+    meta.parse_nested_meta(|inner| {
+        let value = inner.value()?.parse::<syn::LitStr>()?.value();
+        if inner.path.is_ident("serialize") || name.is_none() {
+            name = Some(value);
+        }
+        Ok(())
+    })?;
+    Ok(name)
+}
+
+fn skip_meta_value(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|inner| skip_meta_value(&inner))?;
+    }
+    Ok(())
+}
+
+/// Every invocation of the macro called `name`, including the ones nested in function bodies.
+pub fn macro_invocations(file: &syn::File, name: &str) -> Vec<syn::Macro> {
+    let mut visitor = MacroVisitor {
+        name,
+        macros: vec![],
+    };
+    visitor.visit_file(file);
+    visitor.macros
+}
+
+struct MacroVisitor<'a> {
+    name: &'a str,
+    macros: Vec<syn::Macro>,
+}
+
+impl<'ast> Visit<'ast> for MacroVisitor<'_> {
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        if mac
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == self.name)
+        {
+            self.macros.push(mac.clone());
+        }
+        visit::visit_macro(self, mac);
+    }
+}
+
+/// A handler registered with the router, and the name it is registered under when that
+/// is given explicitly, as in `.append_dyn("patients.list", list_patients.into_dyn())`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub handler: String,
+    pub rpc_name: Option<String>,
+    /// Path of the module the handler is defined in, relative to the registering file, e.g.
+    /// `task_rpc` for `task_rpc::list` or `crate::rpcs::task_rpc` for a handler imported with
+    /// `use crate::rpcs::task_rpc::list`. Empty when it is the registering file's own.
+    pub module: Vec<String>,
+}
+
+/// Handlers registered through `handler.into_dyn()` calls, `router_builder!` invocations
+/// and `.append_dyn("name", ...)` or `.append("name", ...)` chains, in source order and
+/// looking inside macro bodies too.
+pub fn registrations(file: &syn::File) -> Vec<Registration> {
+    let mut visitor = RegistrationVisitor {
+        imports: imports(file),
+        registrations: vec![],
+    };
+    visitor.visit_file(file);
+    visitor.registrations
+}
+
+/// Items brought into scope by the file's `use` items, by the name they are used under,
+/// with their full path. Glob imports can't be resolved.
+fn imports(file: &syn::File) -> HashMap<String, Vec<String>> {
+    fn collect(
+        tree: &syn::UseTree,
+        mut prefix: Vec<String>,
+        imports: &mut HashMap<String, Vec<String>>,
+    ) {
+        match tree {
+            syn::UseTree::Path(path) => {
+                prefix.push(path.ident.to_string());
+                collect(&path.tree, prefix, imports)
+            }
+            // `use module::{self}` imports the module itself.
+            syn::UseTree::Name(name) if name.ident == "self" => {
+                if let Some(module) = prefix.last() {
+                    imports.insert(module.clone(), prefix.clone());
+                }
+            }
+            syn::UseTree::Name(name) => {
+                prefix.push(name.ident.to_string());
+                imports.insert(name.ident.to_string(), prefix);
+            }
+            syn::UseTree::Rename(rename) => {
+                prefix.push(rename.ident.to_string());
+                imports.insert(rename.rename.to_string(), prefix);
+            }
+            syn::UseTree::Group(group) => group
+                .items
+                .iter()
+                .for_each(|tree| collect(tree, prefix.clone(), imports)),
+            syn::UseTree::Glob(_) => {}
+        }
+    }
+
+    let mut imports = HashMap::new();
+    for item in file.items.iter() {
+        if let Item::Use(item) = item {
+            collect(&item.tree, vec![], &mut imports);
+        }
+    }
+    imports
+}
+
+struct RegistrationVisitor {
+    imports: HashMap<String, Vec<String>>,
+    registrations: Vec<Registration>,
+}
+
+impl RegistrationVisitor {
+    fn registration(&self, handler: &Expr, rpc_name: Option<String>) -> Option<Registration> {
+        let mut path = handler_path(handler)?;
+        if let Some(imported) = self.imports.get(&path[0]) {
+            path.splice(0..1, imported.iter().cloned());
+        }
+        if path[0] == "self" {
+            path.remove(0);
+        }
+        let handler = path.pop()?;
+
+        Some(Registration {
+            handler,
+            rpc_name,
+            module: path,
+        })
+    }
+}
+
+impl<'ast> Visit<'ast> for RegistrationVisitor {
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        let args: Vec<&Expr> = call.args.iter().collect();
+        let named = match (call.method.to_string().as_str(), args.as_slice()) {
+            ("append_dyn" | "append", [Expr::Lit(name), handler]) => match &name.lit {
+                syn::Lit::Str(name) => self.registration(handler, Some(name.value())),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(registration) = named {
+            // The receiver is the rest of the chain, registered before this call.
+            self.visit_expr(&call.receiver);
+            self.registrations.push(registration);
+            return;
+        }
+
+        if call.method == "into_dyn" {
+            if let Some(registration) = self.registration(&call.receiver, None) {
+                self.registrations.push(registration);
+            }
+        }
+        visit::visit_expr_method_call(self, call);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let is_router_builder = mac
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "router_builder");
+        if is_router_builder {
+            if let Ok(handlers) = router_builder_handlers(mac) {
+                for handler in handlers {
+                    if let Some(registration) = self.registration(&handler, None) {
+                        self.registrations.push(registration);
+                    }
+                }
+                return;
+            }
+        }
+
+        // Macro bodies are opaque token streams to syn, so try reading them as expressions.
+        if let Ok(args) = comma_separated::<MacroArg>(mac) {
+            for arg in args.iter() {
+                self.visit_expr(&arg.value);
+            }
+        }
+        visit::visit_macro(self, mac);
+    }
+}
+
+/// Segments of `module::handler` in `module::handler` or `module::handler.into_dyn()`.
+fn handler_path(expr: &Expr) -> Option<Vec<String>> {
+    match expr {
+        Expr::Path(path) => Some(
+            path.path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect(),
+        ),
+        Expr::MethodCall(call) if call.method == "into_dyn" => handler_path(&call.receiver),
+        _ => None,
+    }
+}
+
+/// One argument of a macro taking expressions, optionally as `key: value` like the
+/// `handlers: [...]` of `router_builder!`.
+#[derive(Debug, Clone)]
+pub struct MacroArg {
+    pub key: Option<Ident>,
+    pub value: Expr,
+}
+
+impl syn::parse::Parse for MacroArg {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if input.peek(Ident) && input.peek2(Token![:]) && !input.peek2(Token![::]) {
+            let key = input.parse()?;
+            input.parse::<Token![:]>()?;
+            let value = input.parse()?;
+            return Ok(MacroArg {
+                key: Some(key),
+                value,
+            });
+        }
+
+        Ok(MacroArg {
+            key: None,
+            value: input.parse()?,
+        })
+    }
+}
+
+/// Handlers listed by a `router_builder!(a, b)` or `router_builder![handlers: [a, b], ...]`
+/// invocation, as written.
+pub fn router_builder_handlers(mac: &syn::Macro) -> syn::Result<Vec<Expr>> {
+    let args = comma_separated::<MacroArg>(mac)?;
+
+    Ok(args
+        .into_iter()
+        .flat_map(|arg| match (arg.key, arg.value) {
+            (None, handler) => vec![handler],
+            (Some(key), Expr::Array(handlers)) if key == "handlers" => {
+                handlers.elems.into_iter().collect()
+            }
+            _ => vec![],
+        })
+        .collect())
+}
+
+pub fn comma_separated<T: syn::parse::Parse>(mac: &syn::Macro) -> syn::Result<Vec<T>> {
+    Punctuated::<T, Token![,]>::parse_terminated
+        .parse2(mac.tokens.clone())
+        .map(|punctuated| punctuated.into_iter().collect())
+}
+
+/// Line the macro invocation starts on.
+pub fn macro_line(mac: &syn::Macro) -> usize {
+    mac.path
+        .segments
+        .first()
+        .map_or(0, |segment| segment.ident.span().start().line)
+}
+
+pub fn parse_type(ty: &str) -> Result<RustType> {
+    syn::parse_str::<Type>(ty)
+        .map(|ty| rust_type(&ty))
+        .map_err(|e| Error::RustParse(format!("{ty}: {e}")))
+}
+
+pub fn rust_type(ty: &Type) -> RustType {
+    match ty {
+        Type::Path(type_path) => {
+            let segments = type_path
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect();
+            let args = match type_path.path.segments.last().map(|s| &s.arguments) {
+                Some(PathArguments::AngleBracketed(generics)) => generics
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        GenericArgument::Type(ty) => Some(rust_type(ty)),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            };
+            RustType::Path { segments, args }
+        }
+        Type::Tuple(tuple) => RustType::Tuple(tuple.elems.iter().map(rust_type).collect()),
+        Type::Array(array) => RustType::Array(Box::new(rust_type(&array.elem))),
+        Type::Slice(slice) => RustType::Array(Box::new(rust_type(&slice.elem))),
+        Type::Reference(reference) => RustType::Reference(Box::new(rust_type(&reference.elem))),
+        Type::Paren(paren) => rust_type(&paren.elem),
+        Type::Group(group) => rust_type(&group.elem),
+        other => RustType::Other(tokens_to_string(other)),
+    }
+}
+
+/// Renders a type the way it would be written by hand, e.g. `HashMap<String, i64>`.
+pub fn type_to_string(ty: &Type) -> String {
+    tokens_to_string(ty)
+}
+
+/// Name a parameter binds, without `mut` or `ref`. Destructuring a single-field wrapper,
+/// like `Json(params)` or `&(id)`, gives the inner name, other patterns the whole pattern.
+pub fn param_name(pat: &syn::Pat) -> String {
+    match pat {
+        syn::Pat::Ident(pat) => pat.ident.to_string(),
+        syn::Pat::TupleStruct(syn::PatTupleStruct { elems, .. })
+        | syn::Pat::Tuple(syn::PatTuple { elems, .. })
+            if elems.len() == 1 =>
+        {
+            param_name(&elems[0])
+        }
+        syn::Pat::Reference(pat) => param_name(&pat.pat),
+        syn::Pat::Paren(pat) => param_name(&pat.pat),
+        pat => pat_to_string(pat),
+    }
+}
+
+pub fn pat_to_string(pat: &syn::Pat) -> String {
+    tokens_to_string(pat)
+}
+
+fn tokens_to_string(tokens: &impl ToTokens) -> String {
+    tokens
+        .to_token_stream()
+        .to_string()
+        .replace(" < ", "<")
+        .replace("< ", "<")
+        .replace(" <", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+        .replace(" :: ", "::")
+        .replace(":: ", "::")
+        .replace("& ", "&")
+        .replace("( ", "(")
+        .replace(" )", ")")
+        .replace("[ ", "[")
+        .replace(" ]", "]")
+        .replace(" ;", ";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> syn::File {
+        parse_file(Path::new("test_rpc.rs"), source).unwrap()
+    }
+
+    fn named_fields(def: &TypeDef) -> &[Field] {
+        match &def.kind {
+            TypeDefKind::Struct(Fields::Named(fields)) => fields,
+            kind => panic!("expected named fields, got {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn handler_fns_reads_signatures() {
+        let file = parse(
+            r#"
+            use std::collections::HashMap;
+
+            /// Counts the tasks.
+            #[allow(unused)]
+            pub async fn count_tasks<T>(
+                ctx: Ctx, // the caller
+                mm: ModelManager,
+                params: HashMap<String, i64>,
+            ) -> Result<DataRpcResult<HashMap<String, i64>>>
+            where
+                T: Send,
+            {
+                todo!()
+            }
+
+            fn not_a_handler(ctx: Ctx) {}
+
+            mod nested {
+                async fn ping() {}
+            }
+            "#,
+        );
+
+        let handlers = handler_fns(&file);
+        let names: Vec<&str> = handlers.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(names, ["count_tasks", "ping"]);
+
+        let count = &handlers[0];
+        let params: Vec<(String, String)> = count
+            .params
+            .iter()
+            .map(|p| (param_name(&p.pat), type_to_string(&p.ty)))
+            .collect();
+        assert_eq!(
+            params,
+            [
+                ("ctx".to_owned(), "Ctx".to_owned()),
+                ("mm".to_owned(), "ModelManager".to_owned()),
+                ("params".to_owned(), "HashMap<String, i64>".to_owned()),
+            ]
+        );
+        assert_eq!(
+            count.output.as_ref().map(type_to_string).as_deref(),
+            Some("Result<DataRpcResult<HashMap<String, i64>>>")
+        );
+        assert_eq!(count.docs, ["Counts the tasks."]);
+        assert_eq!(count.line, 6);
+        assert!(handlers[1].output.is_none());
+    }
+
+    #[test]
+    fn param_name_unwraps_single_field_patterns() {
+        let name = |pat: &str| param_name(&syn::Pat::parse_single.parse_str(pat).unwrap());
+
+        assert_eq!(name("mut params"), "params");
+        assert_eq!(name("Json(params)"), "params");
+        assert_eq!(name("&(id)"), "id");
+        assert_eq!(name("(a, b)"), "(a, b)");
+    }
+
+    #[test]
+    fn rust_type_strips_wrappers() {
+        assert_eq!(
+            parse_type("(Vec<u8>)").unwrap(),
+            parse_type("Vec<u8>").unwrap()
+        );
+        assert_eq!(
+            parse_type("&[i64]").unwrap(),
+            RustType::Reference(Box::new(RustType::Array(Box::new(
+                parse_type("i64").unwrap()
+            ))))
+        );
+        assert_eq!(
+            parse_type("std::path::PathBuf").unwrap(),
+            RustType::Path {
+                segments: vec!["std".to_owned(), "path".to_owned(), "PathBuf".to_owned()],
+                args: vec![],
+            }
+        );
+        assert!(parse_type("not a type").is_err());
+    }
+
+    #[test]
+    fn type_defs_apply_serde_renames() {
+        let file = parse(
+            r#"
+            #[derive(Serialize)]
+            #[serde(rename_all = "camelCase")]
+            pub struct TaskForCreate {
+                pub task_title: String,
+                #[serde(rename = "done")]
+                pub is_done: bool,
+                pub due_date: Option<String>,
+                #[serde(default, skip_serializing_if = "Vec::is_empty")]
+                pub tag_ids: Vec<i64>,
+                #[serde(skip)]
+                pub secret: String,
+                pub r#type: String,
+            }
+
+            #[derive(Serialize)]
+            #[serde(rename_all = "SCREAMING_SNAKE_CASE", tag = "kind", content = "data")]
+            pub enum TaskEvent {
+                Created(Task),
+                #[serde(rename = "removed")]
+                Deleted { id: i64 },
+                StatusChanged,
+                #[serde(skip)]
+                Internal,
+            }
+
+            #[derive(Serialize)]
+            #[serde(transparent)]
+            pub struct TaskId {
+                id: i64,
+            }
+            "#,
+        );
+
+        let defs = type_defs(&file, Path::new("model.rs"));
+        assert_eq!(defs.len(), 3);
+
+        let fields: Vec<(&str, bool)> = named_fields(&defs[0])
+            .iter()
+            .map(|f| (f.name.as_str(), f.required))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("taskTitle", true),
+                ("done", true),
+                ("dueDate", false),
+                ("tagIds", false),
+                ("type", true),
+            ]
+        );
+
+        let TypeDefKind::Enum { variants, tagging } = &defs[1].kind else {
+            panic!("expected an enum, got {:?}", defs[1].kind);
+        };
+        let names: Vec<&str> = variants.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["CREATED", "removed", "STATUS_CHANGED"]);
+        assert_eq!(
+            *tagging,
+            EnumTagging::Adjacent {
+                tag: "kind".to_owned(),
+                content: "data".to_owned(),
+            }
+        );
+
+        assert_eq!(
+            defs[2].kind,
+            TypeDefKind::Struct(Fields::Newtype(parse_type("i64").unwrap()))
+        );
+    }
+
+    #[test]
+    fn rename_rules() {
+        assert_eq!(rename("due_date", Some("PascalCase"), false), "DueDate");
+        assert_eq!(rename("due_date", Some("kebab-case"), false), "due-date");
+        assert_eq!(rename("due_date", None, false), "due_date");
+        assert_eq!(
+            rename("InProgress", Some("snake_case"), true),
+            "in_progress"
+        );
+        assert_eq!(rename("InProgress", Some("lowercase"), true), "inprogress");
+        assert_eq!(
+            rename("InProgress", Some("SCREAMING-KEBAB-CASE"), true),
+            "IN-PROGRESS"
+        );
+    }
+
+    #[test]
+    fn rpc_markers_from_attributes_and_doc_lines() {
+        let file = parse(
+            r#"
+            #[client_gen::rpc(name = "list_all", client = "patient", method = "patients.list")]
+            async fn list_patients() {}
+
+            #[rpc(unknown = 1, skip)]
+            async fn internal() {}
+
+            /// Lists the notes.
+            /// @rpc name=all method="notes.list"
+            async fn list_notes() {}
+
+            /// @rpcs aren't markers
+            async fn unmarked() {}
+            "#,
+        );
+
+        let handlers = handler_fns(&file);
+        let markers: Vec<Option<RpcMarker>> = handlers.iter().map(|h| h.marker.clone()).collect();
+        assert_eq!(
+            markers,
+            [
+                Some(RpcMarker {
+                    name: Some("list_all".to_owned()),
+                    client: Some("patient".to_owned()),
+                    method: Some("patients.list".to_owned()),
+                    skip: false,
+                }),
+                Some(RpcMarker {
+                    skip: true,
+                    ..Default::default()
+                }),
+                Some(RpcMarker {
+                    name: Some("all".to_owned()),
+                    method: Some("notes.list".to_owned()),
+                    ..Default::default()
+                }),
+                None,
+            ]
+        );
+        assert_eq!(handlers[2].docs, ["Lists the notes."]);
+    }
+
+    #[test]
+    fn router_builder_handlers_in_both_forms() {
+        let file = parse(
+            r#"
+            fn routers() {
+                router_builder!(create_task, task_rpc::list_tasks);
+                router_builder![handlers: [get_task, delete_task.into_dyn()]];
+            }
+            "#,
+        );
+
+        let handlers: Vec<Vec<String>> = macro_invocations(&file, "router_builder")
+            .iter()
+            .map(|mac| {
+                router_builder_handlers(mac)
+                    .unwrap()
+                    .iter()
+                    .map(|handler| handler_path(handler).unwrap().join("::"))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            handlers,
+            [
+                ["create_task", "task_rpc::list_tasks"],
+                ["get_task", "delete_task"]
+            ]
+        );
+    }
+
+    #[test]
+    fn registrations_resolve_modules_and_imports() {
+        let file = parse(
+            r#"
+            use crate::rpcs::note_rpc::{get_note, list_notes as all_notes};
+            use super::category_rpc::*;
+            use super::label_rpc::{self};
+
+            pub fn rpc_router() -> RpcRouter {
+                RpcRouter::new()
+                    .extend(router_builder!(
+                        create_task,
+                        self::update_task,
+                        task_rpc::delete_task.into_dyn(),
+                        crate::rpcs::tag_rpc::list_tags,
+                        get_note.into_dyn(),
+                        label_rpc::list_labels,
+                    ))
+                    .append_dyn("notes.list", all_notes.into_dyn())
+                    .append_dyn("categories.list", list_categories.into_dyn())
+            }
+            "#,
+        );
+
+        let registration = |handler: &str, rpc_name: Option<&str>, module: &[&str]| Registration {
+            handler: handler.to_owned(),
+            rpc_name: rpc_name.map(str::to_owned),
+            module: module.iter().map(|segment| segment.to_string()).collect(),
+        };
+        assert_eq!(
+            registrations(&file),
+            [
+                registration("create_task", None, &[]),
+                registration("update_task", None, &[]),
+                registration("delete_task", None, &["task_rpc"]),
+                registration("list_tags", None, &["crate", "rpcs", "tag_rpc"]),
+                registration("get_note", None, &["crate", "rpcs", "note_rpc"]),
+                registration("list_labels", None, &["super", "label_rpc"]),
+                registration(
+                    "list_notes",
+                    Some("notes.list"),
+                    &["crate", "rpcs", "note_rpc"]
+                ),
+                // Glob imports can't be resolved, so this reads as a local handler.
+                registration("list_categories", Some("categories.list"), &[]),
+            ]
+        );
+    }
+
+    #[test]
+    fn common_rpc_fns_entries() {
+        let file = parse(
+            r#"
+            generate_common_rpc_fns!(
+                Bmc: TaskBmc,
+                Entity: Task,
+                ForCreate: TaskForCreate,
+                Filter: HashMap<String, i64>,
+                Suffix: task
+            );
+            "#,
+        );
+
+        let macros = macro_invocations(&file, "generate_common_rpc_fns");
+        assert_eq!(macros.len(), 1);
+        assert_eq!(macro_line(&macros[0]), 2);
+
+        let entries: Vec<(String, String)> = comma_separated::<MacroKeyValue>(&macros[0])
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.key.to_string(), type_to_string(&entry.value)))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("Bmc".to_owned(), "TaskBmc".to_owned()),
+                ("Entity".to_owned(), "Task".to_owned()),
+                ("ForCreate".to_owned(), "TaskForCreate".to_owned()),
+                ("Filter".to_owned(), "HashMap<String, i64>".to_owned()),
+                ("Suffix".to_owned(), "task".to_owned()),
+            ]
+        );
+    }
+}
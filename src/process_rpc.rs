@@ -1,659 +1,1180 @@
 use std::{
-    borrow::Borrow,
-    cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     fs,
-    path::PathBuf,
-    rc::Rc,
+    path::{Path, PathBuf},
 };
 
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
 use regex::{Regex, RegexBuilder};
 
-use crate::{directories::Directory, util::camel_to_snake, Error, Result};
+use crate::{
+    cache::{content_hash, FileScan, ForeignName, ScanCache},
+    directories::Directory,
+    ir::{Method, Param, RpcModel, RustType, Service, SourceLocation, TypeDef},
+    parser::{self, HandlerFn, Registration},
+    util::{camel_to_snake, pluralize, type_path_matches},
+    Error, Result,
+};
 
-#[derive(Debug, Clone)]
-pub struct OutputFileContent {
-    imports: Vec<String>,
-    bindings: Vec<String>,
-    pub functions: Vec<EntityFunction>,
-}
+pub(crate) fn scan_directory(
+    directory: &Directory,
+    rules: &RpcFileRules,
+    cache: &mut ScanCache,
+) -> Result<RpcModel> {
+    let paths: Vec<&PathBuf> = directory
+        .files
+        .iter()
+        .filter(|path| is_rust_file(path) || is_bindings_file(path))
+        .collect();
 
-impl OutputFileContent {
-    pub fn new<'a>(directory: &'a Directory) -> Result<Self> {
-        let output_file = Rc::new(RefCell::new(OutputFileContent {
-            imports: vec![],
-            bindings: vec![],
-            functions: vec![],
-        }));
+    // Files are scanned in parallel, then merged in discovery order so the model doesn't
+    // depend on which thread finished first.
+    let cache_ref = &*cache;
+    let scans = paths
+        .par_iter()
+        .map(|path| {
+            let content = fs::read_to_string(path)?;
+            let hash = content_hash(content.as_bytes());
+            let scan = match cache_ref.get(path, hash) {
+                Some(scan) => scan,
+                None => scan_file(path, &content, rules)?,
+            };
+            Ok((hash, scan))
+        })
+        .collect::<Result<Vec<(u64, FileScan)>>>()?;
 
-        Self::get_bindings(directory, output_file.clone())?;
-        Self::populate_from_dir(directory, output_file)
+    if !paths.iter().any(|path| rules.is_rpc_file(path)) {
+        eprintln!(
+            "WARNING: No rpc file below {} matches {}, only marked handlers are generated",
+            rules.root.display(),
+            rules.globs.join(", ")
+        );
     }
 
-    pub fn get_bindings<'a>(
-        directory: &'a Directory,
-        output_file: Rc<RefCell<OutputFileContent>>,
-    ) -> Result<Self> {
-        for file in &directory.files {
-            let path = file.to_path_buf();
+    let mut model = RpcModel::default();
+    let mut type_defs = BTreeMap::new();
+    let mut registered = HashMap::new();
+    let mut unregistered = BTreeMap::new();
+    let mut foreign_names: Vec<(PathBuf, ForeignName)> = vec![];
+    for (path, (hash, scan)) in paths.into_iter().zip(scans) {
+        cache.insert(path.clone(), hash, scan.clone());
+        model.bindings.extend(scan.bindings);
+        registered.insert(path.clone(), scan.registered);
+        unregistered.insert(path.clone(), scan.unregistered);
+        foreign_names.extend(
+            scan.foreign_names
+                .into_iter()
+                .filter_map(|name| Some((resolve_module(path, &name.module)?, name))),
+        );
+        for service in scan.services {
+            model.add_service(service);
+        }
+        for def in scan.type_defs {
+            type_defs.entry(def.name.clone()).or_insert(def);
+        }
+    }
 
-            if is_bindings_file(&path) {
-                let mut of = output_file.borrow_mut();
-                of.bindings.append(&mut get_bindings(&path));
+    // Handlers are often registered in a router module away from their definition, so names
+    // given there are applied to the methods of that module still called by their handler's
+    // name, unless the module registers the handler itself.
+    for service in model.services.iter_mut() {
+        for method in service.methods.iter_mut() {
+            let file = &method.location.file;
+            let module = module_path(file);
+            let rpc_name = foreign_names.iter().find_map(|(name_module, name)| {
+                (name.handler == method.rpc_name && *name_module == module)
+                    .then_some(name.rpc_name.as_ref())
+                    .flatten()
+            });
+            let registered_here = registered
+                .get(file)
+                .is_some_and(|handlers| handlers.contains(&method.rpc_name));
+            if let (Some(rpc_name), false) = (rpc_name, registered_here) {
+                method.rpc_name = rpc_name.clone();
             }
         }
+    }
 
-        for dir in &directory.directories {
-            Self::get_bindings(dir, Rc::clone(&output_file))?;
+    // Handlers their own module doesn't register get a method when another one does, under
+    // the first explicit name it gives them if any.
+    let (named, unnamed): (Vec<_>, Vec<_>) = foreign_names
+        .iter()
+        .partition(|(_, name)| name.rpc_name.is_some());
+    for (module, name) in named.into_iter().chain(unnamed) {
+        for (file, methods) in unregistered.iter_mut() {
+            if module_path(file) != *module {
+                continue;
+            }
+            let Some(i) = methods.iter().position(|m| m.name == name.handler) else {
+                continue;
+            };
+            let mut method = methods.remove(i);
+            if let Some(rpc_name) = &name.rpc_name {
+                method.rpc_name = rpc_name.clone();
+            }
+            model.add_service(Service {
+                name: rules.entity(file),
+                methods: vec![method],
+            });
         }
+    }
 
-        Ok((*output_file).clone().into_inner())
+    for def in builtin_type_defs()? {
+        type_defs.entry(def.name.clone()).or_insert(def);
     }
+    model.types = reachable_type_defs(&model, type_defs);
 
-    fn populate_from_dir<'a>(
-        directory: &'a Directory,
-        output_file: Rc<RefCell<OutputFileContent>>,
-    ) -> Result<Self> {
-        for file in &directory.files {
-            let path = file.to_path_buf();
-
-            if is_rpc_file(&path) {
-                let mut of = output_file.borrow_mut();
-                let bindings = of.bindings.clone();
-                of.functions
-                    .append(&mut process_rpc_file(&path, &bindings)?);
-            }
-        }
+    Ok(model)
+}
 
-        for dir in &directory.directories {
-            OutputFileContent::populate_from_dir(dir, Rc::clone(&output_file))?;
-        }
+/// Everything the scan needs from the file at `path`, parsing `content` at most once.
+fn scan_file(path: &Path, content: &str, rules: &RpcFileRules) -> Result<FileScan> {
+    let mut scan = FileScan::default();
 
-        Ok((*output_file).clone().into_inner())
+    if is_bindings_file(path) {
+        scan.bindings = get_bindings(content);
+    }
+    if !is_rust_file(path) {
+        return Ok(scan);
     }
 
-    pub fn write_to_file(self, client_dir: &str, types_dir: &str) {
-        let imports = create_import_statements(self.borrow(), types_dir);
-        let mut function_map: HashMap<String, Vec<String>> = HashMap::new();
-        for function in self.functions.iter() {
-            if !function_map.contains_key(&function.entity) {
-                function_map.insert(function.entity.clone(), vec![function.function.clone()]);
-            } else {
-                function_map
-                    .entry(function.entity.clone())
-                    .and_modify(|e| e.push(function.function.clone()));
-            }
-        }
-        let mut clients = String::from("");
-        for entry in function_map.into_iter() {
-            let client_name = entry.0;
-            let functions: String = entry.1.join("\n");
-            clients += &format!(
-                "\n\nexport const {client_name}_client = {{\n{}\n}};\n",
-                functions
-            );
+    let file = match parser::parse_file(path, content) {
+        Ok(file) => file,
+        Err(e) if !rules.is_rpc_file(path) => {
+            eprintln!("WARNING: Skipping type definitions of {e}");
+            return Ok(scan);
         }
+        Err(e) => return Err(e),
+    };
+    scan.type_defs = parser::type_defs(&file, path);
+
+    let (local, foreign): (Vec<Registration>, Vec<Registration>) = parser::registrations(&file)
+        .into_iter()
+        .partition(|registration| registration.module.is_empty());
+    scan.foreign_names = foreign
+        .into_iter()
+        .map(|registration| ForeignName {
+            module: registration.module,
+            handler: registration.handler,
+            rpc_name: registration.rpc_name,
+        })
+        .collect();
 
-        let final_string_to_write = format!(
-            r#"//*********************************************************************************
-//***THIS FILE IS GENERATED AUTOMATICALLY AND WILL BE OVERWRITTEN. DO NOT MODIFY***
-//*********************************************************************************
+    let mut registered: Vec<Registration> = vec![];
+    if rules.is_rpc_file(path) {
+        check_router_builders(path, &file)?;
+        // A handler registered more than once gets a single method, under the first explicit
+        // name it is registered with if any.
+        for registration in local {
+            match registered
+                .iter_mut()
+                .find(|r| r.handler == registration.handler)
+            {
+                Some(existing) => {
+                    existing.rpc_name = existing.rpc_name.take().or(registration.rpc_name)
+                }
+                None => registered.push(registration),
+            }
+        }
+    }
+    scan.registered = registered
+        .iter()
+        .map(|registration| registration.handler.clone())
+        .collect();
+    scan.unregistered = parser::handler_fns(&file)
+        .iter()
+        .filter(|handler| handler.marker.is_none() && !scan.registered.contains(&handler.name))
+        .map(|handler| get_handler_method(path, handler, rules))
+        .collect();
+    scan.services = process_rpc_file(path, &file, registered, rules)?;
 
-{}
+    Ok(scan)
+}
 
-{}
-            "#,
-            imports, clients,
-        );
-        fs::write(
-            format!("{client_dir}/generated_client.ts"),
-            final_string_to_write,
-        )
-        .unwrap();
+/// Path of the module of the Rust file at `path`, the one its child modules are found
+/// below: its directory for `mod.rs`, `lib.rs` and `main.rs`, itself without the extension
+/// otherwise.
+fn module_path(path: &Path) -> PathBuf {
+    match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some("mod" | "lib" | "main") => path.parent().unwrap_or(path).to_path_buf(),
+        _ => path.with_extension(""),
     }
 }
-fn is_bindings_file(path: &PathBuf) -> bool {
-    let path_str = path.to_str().unwrap();
-    path.is_file() && path_str.contains("bindings.ts") && !path_str.contains("/target/")
-}
-fn get_bindings(path: &PathBuf) -> Vec<String> {
-    let content = fs::read_to_string(path).expect("to be able to read this file into a string");
 
-    let re = RegexBuilder::new(r"export (interface|type) (?<name>\w+) (\{|=)")
-        .build()
-        .unwrap();
+/// Path of the module `module` refers to from the Rust file at `path`, comparable with the
+/// [`module_path`] of the file defining it. `crate` starts from the nearest directory with a
+/// `lib.rs` or `main.rs`, `None` when there is none or `super` goes above the filesystem root.
+fn resolve_module(path: &Path, module: &[String]) -> Option<PathBuf> {
+    let mut resolved = module_path(path);
+    for (i, segment) in module.iter().enumerate() {
+        match segment.as_str() {
+            "crate" if i == 0 => {
+                resolved = path
+                    .ancestors()
+                    .skip(1)
+                    .find(|dir| dir.join("lib.rs").is_file() || dir.join("main.rs").is_file())?
+                    .to_path_buf();
+            }
+            "super" => resolved = resolved.parent()?.to_path_buf(),
+            "self" => {}
+            segment => resolved.push(segment),
+        }
+    }
 
-    re.captures_iter(&content)
-        .filter_map(|e| e.name("name"))
-        .map(|name| name.as_str().to_owned())
-        .collect()
+    Some(resolved)
 }
 
-fn create_import_statements(output_file: &OutputFileContent, types_dir: &str) -> String {
-    let mut imports = Vec::new();
-    imports.push(format!(
-        "import type {{{}}} from \"{types_dir}/bindings\";",
-        output_file
-            .clone()
-            .bindings
-            .into_iter()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect::<Vec<String>>()
-            .join(", ")
-    ));
-    imports.push(format!("export * from \"{types_dir}\";"));
-    imports.push(format!("import {{ baseApiUrl, handleError }} from \".\""));
-
-    imports.push(format!("import {{ Try, Err }} from \"@eman/oxy\";"));
-
-    imports.push(format!(
-        r#"
-export type ListOptions = {{
-  limit?: number,
-  offset?: number,
-  order_bys?: string,
-}};
-
-export type DataRpcResult<T> = {{
-    data: T
-}};
-
-type Option<T> = T | null;
-
-export type RpcResult<T> = {{ id: string, jsonrpc: number, result: DataRpcResult<T> }};
-
-export type ClientErrorValue = {{
-  data: {{
-    detail: ClientError["detail"]
-    req_uuid: string
-  }},
-  message: ClientError["message"]
-}};
-
-export type RpcError = {{ id: string, jsonrpc: number, error: ClientErrorValue }};
-
-export type ParamsIded = {{ id: string }};
-
-export type ParamsForCreate<T> = {{ data: T }};
-
-export type ParamsForUpdate<T> = {{ id: string, data: T }};
-
-export type ParamsList<T> = {{
-  filters?: Partial<Record<keyof T, any>>[],
-  list_options?: {{
-    limit?: number,
-    offset?: number,
-    order_bys?: string,
-  }}
-}};
-
-const reqConfig: RequestInit = {{
-        method: "POST",
-        headers: {{
-          "Content-Type": "application/json",
-        }},
-        credentials: "include",
-      }};
-"#
-    ));
-
-    imports.join("\n")
+/// The params types of rpc-router and the backend's `ListOptions`, for backends that don't
+/// define them in the scanned tree.
+const BUILTIN_TYPES: &str = r#"
+pub struct ParamsIded {
+    pub id: i64,
 }
 
-fn get_route_builder_fns(path: &PathBuf) -> Vec<String> {
-    let mut content = fs::read_to_string(path).expect("to be able to read this file into a string");
-
-    content = content
-        .lines()
-        .map(|l| {
-            if l.trim().starts_with("//") {
-                return "";
-            } else {
-                return l;
-            }
-        })
-        .collect();
-
-    let re_macro = Regex::new(r"router_builder!\(([\s\S]*?)\)").unwrap();
+pub struct ParamsForCreate<D> {
+    pub data: D,
+}
 
-    if let Some(caps) = re_macro.captures(&content) {
-        caps.get(1)
-            .map_or("", |m| m.as_str())
-            .split(",")
-            .map(|s| s.trim().to_owned())
-            .filter(|s| !s.is_empty())
-            .collect()
-    } else {
-        vec![]
-    }
+pub struct ParamsForUpdate<D> {
+    pub id: i64,
+    pub data: D,
 }
 
-fn get_common_rpc_fns(path: &PathBuf) -> Vec<CommonRpcFnsMacroItem> {
-    let content = fs::read_to_string(path).expect("to be able to read this file into a string");
+pub struct ParamsList<F> {
+    pub filters: Option<Vec<F>>,
+    pub list_options: Option<ListOptions>,
+}
 
-    let re_pairs = Regex::new(r"(?m)^\s+(?<name>\w+):\s+(?<entity>\w+),?$").unwrap();
+pub struct ListOptions {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub order_bys: Option<String>,
+}
+"#;
 
-    let re_macro = Regex::new(r"generate_common_rpc_fns!\(([\s\S]*?)\)").unwrap();
+fn builtin_type_defs() -> Result<Vec<TypeDef>> {
+    let path = Path::new("<builtin>");
+    let file = parser::parse_file(path, BUILTIN_TYPES)?;
 
-    let mut macro_items: Vec<CommonRpcFnsMacroItem> = vec![];
+    Ok(parser::type_defs(&file, path))
+}
 
-    if let Some(caps) = re_macro.captures(&content) {
-        let content_inside = caps.get(1).map_or("", |m| m.as_str());
+/// The definitions among `type_defs` that the model's methods use, directly or through
+/// the fields of other definitions.
+fn reachable_type_defs(
+    model: &RpcModel,
+    mut type_defs: BTreeMap<String, TypeDef>,
+) -> BTreeMap<String, TypeDef> {
+    let mut pending: Vec<String> = model
+        .methods()
+        .flat_map(|(_, method)| method.params.iter().map(|p| &p.ty).chain([&method.result]))
+        .flat_map(|ty| ty.names())
+        .map(str::to_owned)
+        .collect();
+    let mut seen = BTreeSet::new();
+    let mut reachable = BTreeMap::new();
 
-        for cap in re_pairs.captures_iter(content_inside) {
-            macro_items.push(CommonRpcFnsMacroItem {
-                name: String::from(&cap[1]),
-                model_type: String::from(&cap[2]),
-            });
+    while let Some(name) = pending.pop() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        if let Some(def) = type_defs.remove(&name) {
+            pending.extend(
+                def.field_types()
+                    .into_iter()
+                    .flat_map(|ty| ty.names())
+                    .map(str::to_owned),
+            );
+            reachable.insert(name, def);
         }
     }
 
-    macro_items
+    reachable
 }
 
-#[derive(Debug)]
-struct CommonRpcFnsMacroItem {
-    name: String,
-    model_type: String,
+fn is_rust_file(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "rs")
 }
 
-#[derive(Debug, Clone)]
-struct EntityFunction {
-    entity: String,
-    function: String,
+fn is_bindings_file(path: &Path) -> bool {
+    path.is_file() && is_bindings_path(path)
 }
 
-#[derive(Debug)]
-struct HandlerParams {
-    params: Vec<Param>,
-    result: String,
+fn is_bindings_path(path: &Path) -> bool {
+    path.to_string_lossy().contains("bindings.ts")
 }
 
-#[derive(Debug)]
-struct Param {
-    name: String,
-    _type: String,
+/// Whether a change to `path` can change the scanned model: any Rust file, as types are
+/// defined outside of rpc files too, or a bindings file. Unlike the checks used while
+/// scanning, this holds for files that were just deleted.
+pub(crate) fn is_scanned_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "rs") || is_bindings_path(path)
 }
 
-fn process_rpc_file(path: &PathBuf, bindings: &Vec<String>) -> Result<Vec<EntityFunction>> {
-    let file_name = path.file_name().unwrap();
-    let entity = file_name
-        .to_str()
-        .to_owned()
-        .unwrap()
-        .replace("_rpc.rs", "");
-    let mut handler_names = get_handler_names_manual(path);
-    handler_names.append(&mut get_route_builder_fns(path));
-    let mut functions = handler_names
-        .iter()
-        .map(|handler_name| {
-            let handler_params: HandlerParams = get_handlers_params(path, handler_name);
-            create_client_from_handler_params(handler_name, &handler_params, bindings)
-        })
-        .filter(|s| s != "")
-        .collect::<Vec<String>>();
+fn get_bindings(content: &str) -> Vec<String> {
+    let re = RegexBuilder::new(r"export (interface|type) (?<name>\w+) (\{|=)")
+        .build()
+        .unwrap();
 
-    functions.push(
-        get_handlers_from_route_builder(path)?
-            .iter()
-            .map(|(handler_name, handler_params)| {
-                create_client_from_handler_params(handler_name, handler_params, bindings)
-            })
-            .collect(),
-    );
-    if functions.len() == 0 {}
-    let mut vec_of_entities: Vec<EntityFunction> = vec![];
-    for function in functions {
-        if function == "" {
-            continue;
-        }
-        vec_of_entities.push(EntityFunction {
-            entity: entity.clone(),
-            function,
-        });
+    re.captures_iter(content)
+        .filter_map(|e| e.name("name"))
+        .map(|name| name.as_str().to_owned())
+        .collect()
+}
+
+/// Fails on a `router_builder!` invocation of the file whose handlers can't be read, as the
+/// registrations skip it.
+fn check_router_builders(path: &Path, file: &syn::File) -> Result<()> {
+    for mac in parser::macro_invocations(file, "router_builder") {
+        parser::router_builder_handlers(&mac)
+            .map_err(|e| Error::RustParse(format!("{}: router_builder!: {e}", path.display())))?;
     }
 
-    Ok(vec_of_entities)
+    Ok(())
 }
 
-fn is_rpc_file(path: &PathBuf) -> bool {
-    let path_str = path.to_str().unwrap();
-    path.is_file()
-        && path_str.contains("lib-rpc")
-        && path_str.contains("_rpc.rs")
-        && !path_str.contains("/target/")
+/// Every `generate_common_rpc_fns!` invocation of the file.
+fn get_common_rpc_fns(path: &Path, file: &syn::File) -> Result<Vec<CommonRpcFns>> {
+    parser::macro_invocations(file, "generate_common_rpc_fns")
+        .iter()
+        .map(|mac| {
+            let items = parser::comma_separated::<parser::MacroKeyValue>(mac).map_err(|e| {
+                Error::RustParse(format!("{}: generate_common_rpc_fns!: {e}", path.display()))
+            })?;
+            CommonRpcFns::new(path, &items, parser::macro_line(mac))
+        })
+        .collect()
 }
 
-fn type_exists_in_bindings(type_to_check: &str, bindings: &Vec<String>) -> bool {
-    if type_to_check == "null" || type_to_check == "String" {
-        return true;
-    }
-    for binding in bindings.iter() {
-        if binding == type_to_check || type_to_check.contains(&format!("<{binding}>")) {
-            return true;
+/// The entries of a `generate_common_rpc_fns!` invocation, which generates the
+/// `create_<suffix>`, `get_<suffix>`, `list_<plural>`, `update_<suffix>` and
+/// `delete_<suffix>` handlers of an entity.
+#[derive(Debug)]
+struct CommonRpcFns {
+    entity: String,
+    for_create: Option<String>,
+    for_update: Option<String>,
+    filter: Option<String>,
+    /// Snake case name of the entity in the handler names.
+    suffix: String,
+    /// `Plural`, the suffix's plural when English rules get it wrong.
+    plural: Option<String>,
+    line: usize,
+}
+
+impl CommonRpcFns {
+    fn new(path: &Path, items: &[parser::MacroKeyValue], line: usize) -> Result<Self> {
+        let mut entity = None;
+        let mut suffix = None;
+        let mut fns = CommonRpcFns {
+            entity: String::new(),
+            for_create: None,
+            for_update: None,
+            filter: None,
+            suffix: String::new(),
+            plural: None,
+            line,
+        };
+
+        for item in items {
+            let value = parser::type_to_string(&item.value);
+            match item.key.to_string().as_str() {
+                "Bmc" => {}
+                "Entity" => entity = Some(value),
+                "ForCreate" => fns.for_create = Some(value),
+                "ForUpdate" => fns.for_update = Some(value),
+                "Filter" => fns.filter = Some(value),
+                "Suffix" => suffix = Some(camel_to_snake(&value)),
+                "Plural" => fns.plural = Some(camel_to_snake(&value)),
+                key => {
+                    return Err(Error::UnknownCommonRpcFnsEntry(format!(
+                        "{}: {key}",
+                        path.display()
+                    )))
+                }
+            }
         }
+
+        fns.entity =
+            entity.ok_or_else(|| Error::EntityMissingFromRpcFns(path.display().to_string()))?;
+        fns.suffix =
+            suffix.ok_or_else(|| Error::SuffixMissingFromRpcFns(path.display().to_string()))?;
+
+        Ok(fns)
+    }
+
+    fn plural(&self) -> String {
+        self.plural
+            .clone()
+            .unwrap_or_else(|| pluralize(&self.suffix))
+    }
+
+    fn is_list_handler(&self, handler_name: &str) -> bool {
+        // Older versions of the macro pluralise by appending an `s`, whatever the suffix.
+        handler_name == format!("list_{}", self.plural())
+            || handler_name == format!("list_{}s", self.suffix)
     }
-    return false;
 }
 
-fn create_client_from_handler_params(
-    handler_name: &str,
-    handler_params: &HandlerParams,
-    bindings: &Vec<String>,
-) -> String {
-    let first_param = &handler_params.params.get(0);
-
-    let return_type = &handler_params.result;
-    if first_param.is_none() {
-        return "".to_owned();
-    }
-
-    let handler_param_type = &first_param.unwrap()._type;
-
-    // let regex = Regex::new("(ParamsForCreate|ParamsList|ParamsForUpdate)<(?P<Entity>.*)>").unwrap();
-    // let caps = regex.captures(&handler_param_type);
-    // let mut client_param_type = {
-    //     let mut s = String::from("");
-    //     if let Some(caps) = caps {
-    //         let thing = &caps.name("Entity");
-    //
-    //         if let Some(name) = thing {
-    //             let name = name.as_str();
-    //             s = name.to_owned();
-    //         }
-    //     }
-    //     s
-    // };
-    //
-
-    let client_param_type = handler_param_type
-        .replace("Vec", "Array")
-        .replace("i64", "string")
-        .replace("()", "null");
-
-    let mut client_param_name = "params".to_owned();
-
-    // if client_param_type.starts_with("Vec<") {
-    //     let vec_regex = Regex::new("Vec<(?P<entity>.*)>").unwrap();
-    //     let caps = vec_regex.captures(&client_param_type);
-    //
-    //     if let Some(caps) = caps {
-    //         let t = &caps.name("entity");
-    //
-    //         if let Some(name) = t {
-    //             let name = name.as_str();
-    //             let mut lower_case_name = camel_to_snake(name);
-    //             // FIXME: this is not a sufficient check for good naming but itll do for now
-    //             let length = lower_case_name.split("y_").collect::<Vec<&str>>().len();
-    //             if length < 3 {
-    //                 lower_case_name = lower_case_name.replace("y_", "ies_");
-    //                 client_param_name = lower_case_name;
-    //             } else {
-    //                 client_param_name = lower_case_name;
-    //             }
-    //         }
-    //     }
-    // }
-
-    let mut client_return_type = String::from("null");
-
-    let vec_regex = Regex::new("<DataRpcResult<(?P<entity>.*)>>").unwrap();
-    let caps = vec_regex.captures(&return_type);
-
-    if let Some(caps) = caps {
-        let t = &caps.name("entity");
-
-        if let Some(name) = t {
-            let return_type = name
-                .as_str()
-                .replace("Vec", "Array")
-                .replace("i64", "string")
-                .replace("()", "null");
-
-            client_return_type = return_type
+/// Services of the handlers in `file`: for rpc files those `registered` with the router and
+/// generated by `generate_common_rpc_fns!`, and in any file those marked with `#[rpc]` or
+/// `/// @rpc`.
+fn process_rpc_file(
+    path: &Path,
+    file: &syn::File,
+    registered: Vec<Registration>,
+    rules: &RpcFileRules,
+) -> Result<Vec<Service>> {
+    let is_rpc_file = rules.is_rpc_file(path);
+    let entity = rules.entity(path);
+    let handler_fns = parser::handler_fns(file);
+    let handlers: HashMap<&str, &HandlerFn> = handler_fns
+        .iter()
+        .map(|handler| (handler.name.as_str(), handler))
+        .collect();
+
+    let marked = handler_fns
+        .iter()
+        .filter(|handler| {
+            handler.marker.is_some() && !registered.iter().any(|r| r.handler == handler.name)
+        })
+        .map(|handler| Registration {
+            handler: handler.name.clone(),
+            rpc_name: None,
+            module: vec![],
+        })
+        .collect::<Vec<_>>();
+
+    let common_rpc_fns = match is_rpc_file {
+        true => get_common_rpc_fns(path, file)?,
+        false => vec![],
+    };
+
+    let mut services = vec![Service {
+        name: entity.clone(),
+        methods: vec![],
+    }];
+    for registration in registered.iter().chain(marked.iter()) {
+        let Some(handler) = handlers.get(registration.handler.as_str()) else {
+            // Registered handlers not defined in the file come from `generate_common_rpc_fns!`.
+            if let Some(method) = get_generated_method(path, &common_rpc_fns, registration)? {
+                services[0].methods.push(method);
+            }
+            continue;
+        };
+        let marker = handler.marker.clone().unwrap_or_default();
+        if marker.skip {
+            continue;
+        }
+
+        let mut method = get_handler_method(path, handler, rules);
+        if let Some(rpc_name) = registration.rpc_name.clone().or(marker.method) {
+            method.rpc_name = rpc_name;
+        }
+        if let Some(name) = marker.name {
+            method.name = name;
+        }
+        let client = marker.client.unwrap_or_else(|| entity.clone());
+        match services.iter_mut().find(|service| service.name == client) {
+            Some(service) => service.methods.push(method),
+            None => services.push(Service {
+                name: client,
+                methods: vec![method],
+            }),
         }
     }
 
-    let mut params_object = String::from("");
-
-    // if handler_param_type.contains("ParamsForCreate") {
-    //     client_param_name = "params".to_owned();
-    //     params_object += &format!("...params");
-    // };
-    // if handler_param_type.contains("ParamsForUpdate") {
-    //     params_object += &format!("\"id\": params.id,");
-    //     params_object += &format!("\n\t    \"data\": params.data,");
-    // };
-    // if handler_param_type.contains("ParamsList") {
-    //     client_param_name = "params".to_owned();
-    //     params_object += &format!("...params,");
-    // };
-    // if handler_param_type.contains("ParamsIded") {
-    //     params_object += &format!("\"id\": params.id");
-    //     client_param_name = "params".to_owned();
-    // }
-
-    // if handler_param_type.contains("ListOptions") {
-    //     params_object += &format!("\"list_options\": {client_param_name}");
-    //     client_param_type = handler_param_type.to_owned();
-    // }
-
-    let colon = if !client_param_name.is_empty() {
-        ": "
-    } else {
-        ""
-    };
+    if !is_rpc_file {
+        services.retain(|service| !service.methods.is_empty());
+    }
 
-    // if handler_name == "list_patients" {
-    //     println!("{handler_params:#?}");
-    //     println!("client_param_name: {client_param_name}");
-    //     println!("hadnler_param_type: {handler_param_type:#?}");
-    //     println!("params: {params_object}")
-    // }
-
-    // FIXME: figure this out later
-    // if client_param_type.contains("ParamsForUpdate<"){
-    //     params_object += format!("\"id\": {client_param_name}");
-    // }
-    //
-    let function = format!(
-        r#"    async {handler_name}({client_param_name}{colon}{client_param_type}) {{
-      const happyPath = async () => fetch(`${{baseApiUrl}}/api/rpc`, {{
-        ...reqConfig,
-        body: JSON.stringify({{
-          id: 1,
-          jsonrpc: "2.0",
-          method: "{handler_name}",
-          params: {{
-            ...params 
-          }},
-        }}),
-      }}) as unknown as Promise<RpcResult<{client_return_type}>>;
-      const val = await Try(happyPath, (e: RpcError) => Err(e));
-      if (val.isError && handleError){{
-          handleError(val);
-      }}
-      return val;
-    }},
-"#
-    );
-
-    // if !type_exists_in_bindings(&client_param_type, bindings)
-    //     || !type_exists_in_bindings(&client_return_type, bindings)
-    // {
-    //     if handler_param_type.contains("ParamsList") {
-    //         println!("ParamsList!");
-    //     }
-    //     println!("WARNING! Skipping handler: {handler_name}. Either {client_param_type} or {client_return_type} does not exist in bindings");
-    //     return "".to_owned();
-    // }
-    //
-    function
+    Ok(services)
 }
-fn get_handler_names_manual(path: &PathBuf) -> Vec<String> {
-    let content = fs::read_to_string(path).expect("to be able to read this file into a string");
 
-    let re = RegexBuilder::new(r"(?<name>\w+)\s*\.into_dyn()")
-        .build()
-        .unwrap();
+pub(crate) const DEFAULT_RPC_FILES: &[&str] = &["**/*lib-rpc*/**/*_rpc.rs"];
+pub(crate) const DEFAULT_ENTITY_PATTERN: &str = r"(?<entity>[^/]+)_rpc\.rs$";
+pub(crate) const DEFAULT_RESOURCE_TYPES: &[&str] = &["Ctx", "ModelManager", "State"];
 
-    re.captures_iter(&content)
-        .filter_map(|e| e.name("name"))
-        .map(|name| name.as_str().to_owned())
-        .collect()
+/// Which files hold handlers, and the entity each of them is named after.
+#[derive(Debug, Clone)]
+pub(crate) struct RpcFileRules {
+    root: PathBuf,
+    globs: Vec<String>,
+    files: GlobSet,
+    entity: Regex,
+    resource_types: Vec<String>,
+    /// The globs and pattern the rules were built from, scans cached under other rules
+    /// can't be reused.
+    key: String,
 }
 
-fn get_handlers_from_route_builder(path: &PathBuf) -> Result<Vec<(String, HandlerParams)>> {
-    let common_rpc_fns = get_common_rpc_fns(path);
+impl RpcFileRules {
+    /// `globs` are matched against paths relative to `root` and full paths. `entity_pattern`
+    /// is searched for in the relative path, the entity being its `entity` group, or its
+    /// first group if it has no `entity` group. Handler params of one of the `resource_types`
+    /// are provided by the server and left out of the client.
+    pub fn new(
+        root: &Path,
+        globs: &[String],
+        entity_pattern: &str,
+        resource_types: &[String],
+    ) -> Result<Self> {
+        let mut files = GlobSetBuilder::new();
+        for glob in globs {
+            files.add(
+                Glob::new(glob)
+                    .map_err(|e| Error::InvalidConfig(format!("rpc_files {glob}: {e}")))?,
+            );
+        }
+        let files = files
+            .build()
+            .map_err(|e| Error::InvalidConfig(e.to_string()))?;
+        let entity = Regex::new(entity_pattern)
+            .map_err(|e| Error::InvalidConfig(format!("entity_pattern: {e}")))?;
+
+        // Whether a file is an rpc file and its entity depend on its path relative to the
+        // root, so scans below another root can't be reused.
+        let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+        Ok(RpcFileRules {
+            root: root.to_path_buf(),
+            globs: globs.to_vec(),
+            files,
+            entity,
+            resource_types: resource_types.to_vec(),
+            key: format!(
+                "{} {globs:?} {entity_pattern} {resource_types:?}",
+                canonical_root.display()
+            ),
+        })
+    }
 
-    let macro_items = get_route_builder_fns(path);
+    pub fn key(&self) -> &str {
+        &self.key
+    }
 
-    if common_rpc_fns.len() == 0 {
-        return Ok(vec![]);
+    /// Whether `ty` is provided by the server, through references, matching one of the
+    /// resource types as described by [`type_path_matches`].
+    fn is_resource(&self, ty: &RustType) -> bool {
+        match ty {
+            RustType::Reference(inner) => self.is_resource(inner),
+            RustType::Path { segments, .. } => self
+                .resource_types
+                .iter()
+                .any(|key| type_path_matches(key, segments)),
+            _ => false,
+        }
     }
 
-    let entity = &common_rpc_fns.iter().find(|f| &f.name == "Entity").ok_or(
-        Error::EntityMissingFromRpcFns(path.to_str().unwrap().to_owned()),
-    )?;
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
 
-    let suffix = &common_rpc_fns
-        .iter()
-        .find(|f| &f.name == "Entity")
-        .ok_or(Error::SuffixMissingFromRpcFns)?;
+    /// Whether `path` matches one of the globs, relative to the root or as a full path. The
+    /// latter lets `**/` globs see the directories above the root, so the default rule still
+    /// finds the handlers when the root is the `lib-rpc` crate itself.
+    fn is_rpc_path(&self, path: &Path) -> bool {
+        self.files.is_match(self.relative(path))
+            || std::path::absolute(path).is_ok_and(|path| self.files.is_match(path))
+    }
 
-    let mut handlers: Vec<(String, HandlerParams)> = vec![];
-    for handler_name in macro_items {
-        let return_type =
-            get_builder_item_return_type(&handler_name, &suffix.model_type, &entity.model_type);
+    fn is_rpc_file(&self, path: &Path) -> bool {
+        path.is_file() && self.is_rpc_path(path)
+    }
 
-        if let Err(Error::CantMatchHandlerReturnType(e)) = return_type {
-            println!("WARNING: Handler: {handler_name}, Error: Cant match handler return type. Ignoring as it might be a function defined outside of the generate_common_rpc_fns macro.");
-            continue;
-        } else {
-            let return_type = return_type.unwrap();
-            let params =
-                get_builder_item_params(&handler_name, &suffix.model_type, &common_rpc_fns)?;
-
-            handlers.push((
-                handler_name,
-                HandlerParams {
-                    params: vec![Param {
-                        name: "params".to_owned(),
-                        _type: params,
-                    }],
-                    result: return_type,
-                },
-            ));
-        }
+    /// Entity the handlers of the rpc file at `path` are grouped under, the file stem if
+    /// the pattern doesn't match.
+    fn entity(&self, path: &Path) -> String {
+        let relative = self.relative(path).to_string_lossy().replace('\\', "/");
+
+        self.entity
+            .captures(&relative)
+            .and_then(|captures| captures.name("entity").or_else(|| captures.get(1)))
+            .map(|entity| entity.as_str().to_owned())
+            .unwrap_or_else(|| {
+                path.file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            })
     }
+}
 
-    Ok(handlers)
+/// The method `generate_common_rpc_fns!` generates for `registration`, if any.
+fn get_generated_method(
+    path: &Path,
+    common_rpc_fns: &[CommonRpcFns],
+    registration: &Registration,
+) -> Result<Option<Method>> {
+    if common_rpc_fns.is_empty() {
+        return Ok(None);
+    }
+
+    let handler_name = &registration.handler;
+    let Some((fns, return_type)) = common_rpc_fns.iter().find_map(|fns| {
+        get_builder_item_return_type(handler_name, fns)
+            .ok()
+            .map(|return_type| (fns, return_type))
+    }) else {
+        eprintln!("WARNING: Handler: {handler_name}, Error: Cant match handler return type. Ignoring as it might be a function defined outside of the generate_common_rpc_fns macro.");
+        return Ok(None);
+    };
+    let params = get_builder_item_params(handler_name, fns)?;
+
+    Ok(Some(Method {
+        name: handler_name.clone(),
+        rpc_name: registration
+            .rpc_name
+            .clone()
+            .unwrap_or_else(|| handler_name.clone()),
+        params: vec![Param {
+            name: "params".to_owned(),
+            ty: parser::parse_type(&params)?,
+        }],
+        result: parser::parse_type(&return_type)?,
+        docs: vec![],
+        location: SourceLocation {
+            file: path.to_path_buf(),
+            line: fns.line,
+        },
+    }))
 }
 
-fn get_builder_item_return_type(
-    handler_name: &str,
-    capital_suffix: &str,
-    entity: &str,
-) -> Result<String> {
-    let suffix = camel_to_snake(capital_suffix);
-    let get_handler = format!("get_{}", suffix);
-    let create_handler = format!("create_{}", suffix);
-    let delete_handler = format!("delete_{}", suffix);
-    let update_handler = format!("update_{}", suffix);
-    let list_handler = format!("list_{}s", suffix);
-
-    if handler_name == get_handler
-        || handler_name == create_handler
-        || handler_name == delete_handler
-        || handler_name == update_handler
+fn get_builder_item_return_type(handler_name: &str, fns: &CommonRpcFns) -> Result<String> {
+    let suffix = &fns.suffix;
+    let entity = &fns.entity;
+
+    if handler_name == format!("get_{suffix}")
+        || handler_name == format!("create_{suffix}")
+        || handler_name == format!("delete_{suffix}")
+        || handler_name == format!("update_{suffix}")
     {
-        Ok(format!("Result<DataRpcResult<{}>>", entity))
-    } else if handler_name == list_handler {
-        Ok(format!("Result<DataRpcResult<Vec<{}>>>", entity))
+        Ok(format!("Result<DataRpcResult<{entity}>>"))
+    } else if fns.is_list_handler(handler_name) {
+        Ok(format!("Result<DataRpcResult<Vec<{entity}>>>"))
     } else {
         Err(Error::CantMatchHandlerReturnType(handler_name.to_owned()))
     }
 }
 
-fn get_builder_item_params(
-    handler_name: &str,
-    capital_suffix: &str,
-    common_rpc_macro_items: &Vec<CommonRpcFnsMacroItem>,
-) -> Result<String> {
-    let suffix = camel_to_snake(capital_suffix);
-    let get_handler = format!("get_{}", suffix);
-    let create_handler = format!("create_{}", suffix);
-    let delete_handler = format!("delete_{}", suffix);
-    let update_handler = format!("update_{}", suffix);
-    let list_handler = format!("list_{}s", suffix);
-
-    if handler_name == get_handler || handler_name == delete_handler {
+fn get_builder_item_params(handler_name: &str, fns: &CommonRpcFns) -> Result<String> {
+    let suffix = &fns.suffix;
+
+    if handler_name == format!("get_{suffix}") || handler_name == format!("delete_{suffix}") {
         Ok("ParamsIded".to_string())
-    } else if handler_name == create_handler {
-        let fc = common_rpc_macro_items
-            .iter()
-            .find(|f| &f.name == "ForCreate")
+    } else if handler_name == format!("create_{suffix}") {
+        let for_create = fns
+            .for_create
+            .as_ref()
             .ok_or(Error::ForCreateMissingFromRpcFns)?;
-        Ok(format!("ParamsForCreate<{}>", fc.model_type))
-    } else if handler_name == update_handler {
-        let fu = common_rpc_macro_items
-            .iter()
-            .find(|f| &f.name == "ForUpdate")
+        Ok(format!("ParamsForCreate<{for_create}>"))
+    } else if handler_name == format!("update_{suffix}") {
+        let for_update = fns
+            .for_update
+            .as_ref()
             .ok_or(Error::ForUpdateMissingFromRpcFns)?;
-        Ok(format!("ParamsForUpdate<{}>", fu.model_type))
-    } else if handler_name == list_handler {
-        let filter = common_rpc_macro_items
-            .iter()
-            .find(|f| &f.name == "Filter")
-            .ok_or(Error::FilterMissingFromRpcFns)?;
-        Ok(format!("ParamsList<{}>", filter.model_type))
+        Ok(format!("ParamsForUpdate<{for_update}>"))
+    } else if fns.is_list_handler(handler_name) {
+        let filter = fns.filter.as_ref().ok_or(Error::FilterMissingFromRpcFns)?;
+        Ok(format!("ParamsList<{filter}>"))
     } else {
         Err(Error::CantMatchHandlerParams(handler_name.to_owned()))
     }
 }
 
-fn get_handlers_params(path: &PathBuf, handler_name: &str) -> HandlerParams {
-    let content = fs::read_to_string(path).expect("to be able to read this file into a string");
-
-    let pattern_string = format!(
-        r"(?s)async fn {}\((?P<params>.*?)\)\s*->\s(?P<result>.*?)\s*\{{",
-        regex::escape(&handler_name)
-    );
-
-    let re = RegexBuilder::new(&pattern_string).build().unwrap();
-    let ctx_mm_remove_regex = Regex::new(r",?\s*(ctx: Ctx|mm: ModelManager)\s*,?").unwrap();
-    let params = re
-        .captures_iter(&content)
-        .filter_map(|e| e.name("params"))
-        .map(|name| {
-            let name = name.as_str().to_owned().trim().to_owned();
-            ctx_mm_remove_regex
-                .replace_all(&name, "")
-                .trim()
-                .to_string()
+fn get_handler_method(path: &Path, handler: &HandlerFn, rules: &RpcFileRules) -> Method {
+    let params = handler
+        .params
+        .iter()
+        .map(|param| Param {
+            name: parser::param_name(&param.pat),
+            ty: parser::rust_type(&param.ty),
         })
-        .flat_map(|s| {
-            s.split(",")
-                .filter(|s| *s != "")
-                .map(|s| {
-                    let param_type = s.split(": ").map(|s| s.to_owned()).collect::<Vec<String>>();
-                    Param {
-                        name: param_type[0].clone(),
-                        _type: param_type[1].clone(),
+        .filter(|param| !rules.is_resource(&param.ty))
+        .collect();
+
+    let result = handler
+        .output
+        .as_ref()
+        .map_or(RustType::unit(), parser::rust_type);
+
+    Method {
+        name: handler.name.clone(),
+        rpc_name: handler.name.clone(),
+        params,
+        result,
+        docs: handler.docs.clone(),
+        location: SourceLocation {
+            file: path.to_path_buf(),
+            line: handler.line,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tree of its own for each test holding `files`, given by path relative to its root.
+    fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("client_gen_scan_{name}"));
+        let _ = fs::remove_dir_all(&root);
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    /// Scans `root` with the default rules and no cache.
+    fn scan(root: &Path) -> RpcModel {
+        let directory = Directory::new(root, &[], &[]).unwrap();
+        let rules = RpcFileRules::new(
+            root,
+            &DEFAULT_RPC_FILES
+                .iter()
+                .map(|g| g.to_string())
+                .collect::<Vec<_>>(),
+            DEFAULT_ENTITY_PATTERN,
+            &DEFAULT_RESOURCE_TYPES
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        scan_directory(&directory, &rules, &mut ScanCache::default()).unwrap()
+    }
+
+    /// `(client, method, rpc name)` of every method of `model`.
+    fn methods(model: &RpcModel) -> Vec<(&str, &str, &str)> {
+        model
+            .methods()
+            .map(|(service, method)| {
+                (
+                    service.name.as_str(),
+                    method.name.as_str(),
+                    method.rpc_name.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    const TASK_RPC: &str = r#"
+        pub fn rpc_router() -> RpcRouter {
+            router_builder!(list_tasks)
+        }
+
+        pub async fn list_tasks(ctx: Ctx, mm: ModelManager) -> Result<DataRpcResult<Vec<Task>>> {
+            todo!()
+        }
+    "#;
+
+    #[test]
+    fn handlers_registered_twice_once_under_their_explicit_name() {
+        let root = fixture(
+            "registered_twice",
+            &[(
+                "lib-rpc/src/patient_rpc.rs",
+                r#"
+                pub fn rpc_router() -> RpcRouter {
+                    router_builder!(list_patients)
+                        .append_dyn("patients.list", list_patients.into_dyn())
+                }
+
+                pub async fn list_patients(ctx: Ctx) -> Result<DataRpcResult<Vec<Patient>>> {
+                    todo!()
+                }
+                "#,
+            )],
+        );
+
+        assert_eq!(
+            methods(&scan(&root)),
+            [("patient", "list_patients", "patients.list")]
+        );
+    }
+
+    #[test]
+    fn handlers_registered_by_a_router_module() {
+        let root = fixture(
+            "router_module",
+            &[
+                ("lib-rpc/src/lib.rs", "pub mod rpcs;"),
+                (
+                    "lib-rpc/src/rpcs/mod.rs",
+                    r#"
+                    use crate::rpcs::note_rpc::list_notes;
+
+                    pub fn rpc_router() -> RpcRouter {
+                        RpcRouter::new()
+                            .append("patients.list", patient_rpc::list_patients)
+                            .extend(router_builder!(
+                                list_notes.into_dyn(),
+                                task_rpc::list_tasks.into_dyn(),
+                            ))
+                    }
+                    "#,
+                ),
+                (
+                    "lib-rpc/src/rpcs/note_rpc.rs",
+                    "pub async fn list_notes(ctx: Ctx) -> Result<DataRpcResult<Vec<Note>>> {}",
+                ),
+                (
+                    "lib-rpc/src/rpcs/patient_rpc.rs",
+                    r#"
+                    pub async fn list_patients(ctx: Ctx) -> Result<DataRpcResult<Vec<Patient>>> {
+                        todo!()
                     }
-                })
-                .collect::<Vec<Param>>()
-        })
-        .collect::<Vec<Param>>();
 
-    // .flat_map(|s| s.split(",").collect::<Vec<String>>())
-    // .collect::<Vec<Vec<String>>>();
-    let result = re
-        .captures_iter(&content)
-        .filter_map(|e| e.name("result"))
-        .map(|name| name.as_str().to_owned())
-        .collect::<String>();
+                    async fn unregistered(ctx: Ctx) -> Result<()> {
+                        todo!()
+                    }
+                    "#,
+                ),
+                ("lib-rpc/src/rpcs/task_rpc.rs", TASK_RPC),
+            ],
+        );
+
+        assert_eq!(
+            methods(&scan(&root)),
+            [
+                ("note", "list_notes", "list_notes"),
+                ("patient", "list_patients", "patients.list"),
+                ("task", "list_tasks", "list_tasks"),
+            ]
+        );
+    }
+
+    #[test]
+    fn handlers_listed_by_path_in_a_router_builder() {
+        let root = fixture(
+            "router_builder_paths",
+            &[
+                ("lib-rpc/src/lib.rs", "pub mod router; pub mod rpcs;"),
+                (
+                    "lib-rpc/src/router.rs",
+                    r#"
+                    pub fn rpc_router() -> RpcRouter {
+                        router_builder!(crate::rpcs::note_rpc::list_notes)
+                    }
+                    "#,
+                ),
+                (
+                    "lib-rpc/src/rpcs/mod.rs",
+                    r#"
+                    pub fn rpc_router() -> RpcRouter {
+                        router_builder![handlers: [task_rpc::list_tasks]]
+                    }
+                    "#,
+                ),
+                (
+                    "lib-rpc/src/rpcs/note_rpc.rs",
+                    "pub async fn list_notes(ctx: Ctx) -> Result<DataRpcResult<Vec<Note>>> {}",
+                ),
+                (
+                    "lib-rpc/src/rpcs/task_rpc.rs",
+                    "pub async fn list_tasks(ctx: Ctx) -> Result<DataRpcResult<Vec<Task>>> {}",
+                ),
+            ],
+        );
+
+        assert_eq!(
+            methods(&scan(&root)),
+            [
+                ("note", "list_notes", "list_notes"),
+                ("task", "list_tasks", "list_tasks"),
+            ]
+        );
+    }
+
+    #[test]
+    fn router_modules_only_name_the_handlers_of_their_own_crate() {
+        let router = |prefix: &str| {
+            format!(
+                r#"
+                pub fn rpc_router() -> RpcRouter {{
+                    RpcRouter::new()
+                        .append_dyn("{prefix}.tasks", task_rpc::list_tasks.into_dyn())
+                        .append_dyn("{prefix}.task", super::rpcs::task_rpc::get_task.into_dyn())
+                }}
+                "#
+            )
+        };
+        let task_rpc = r#"
+            pub fn rpc_router() -> RpcRouter {
+                router_builder!(get_task)
+            }
+
+            pub async fn get_task(ctx: Ctx, id: i64) -> Result<DataRpcResult<Task>> {}
+
+            pub async fn list_tasks(ctx: Ctx) -> Result<DataRpcResult<Vec<Task>>> {}
+        "#;
+        let (admin, billing) = (router("admin"), router("billing"));
+        let root = fixture(
+            "router_modules_per_crate",
+            &[
+                ("admin-lib-rpc/src/lib.rs", "pub mod rpcs;"),
+                ("admin-lib-rpc/src/rpcs/mod.rs", &admin),
+                ("admin-lib-rpc/src/rpcs/task_rpc.rs", task_rpc),
+                ("billing-lib-rpc/src/lib.rs", "pub mod rpcs;"),
+                ("billing-lib-rpc/src/rpcs/mod.rs", &billing),
+                ("billing-lib-rpc/src/rpcs/task_rpc.rs", task_rpc),
+            ],
+        );
+
+        // Both `task_rpc.rs` share a stem, yet each is only named by its own crate's router.
+        assert_eq!(
+            methods(&scan(&root)),
+            [
+                ("task", "get_task", "get_task"),
+                ("task", "get_task", "get_task"),
+                ("task", "list_tasks", "admin.tasks"),
+                ("task", "list_tasks", "billing.tasks"),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_resource_params_by_name_or_path() {
+        let root = fixture(
+            "resource_params",
+            &[(
+                "lib-rpc/src/task_rpc.rs",
+                r#"
+                pub fn rpc_router() -> RpcRouter {
+                    router_builder!(get_task, list_tasks, update_task, count_tasks)
+                }
+
+                pub async fn get_task(_ctx: Ctx, mm: &ModelManager, id: i64) -> Result<Task> {}
+                pub async fn list_tasks(State(s): State<AppState>, filter: Filter) -> Result<()> {}
+                pub async fn update_task(ctx: crate::ctx::Ctx, data: TaskForUpdate) -> Result<()> {}
+                pub async fn count_tasks(ctx: &&Ctx, session: Session) -> Result<u32> {}
+                "#,
+            )],
+        );
+
+        let model = scan(&root);
+        let params: Vec<(&str, Vec<&str>)> = model
+            .methods()
+            .map(|(_, method)| {
+                let names = method.params.iter().map(|p| p.name.as_str()).collect();
+                (method.name.as_str(), names)
+            })
+            .collect();
+        assert_eq!(
+            params,
+            [
+                ("get_task", vec!["id"]),
+                ("list_tasks", vec!["filter"]),
+                ("update_task", vec!["data"]),
+                ("count_tasks", vec!["session"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn full_path_resource_types_only_match_full_paths() {
+        let rules = RpcFileRules::new(
+            Path::new("."),
+            &[],
+            DEFAULT_ENTITY_PATTERN,
+            &["ctx::Ctx".to_owned()],
+        )
+        .unwrap();
+        let is_resource = |ty: &str| rules.is_resource(&parser::parse_type(ty).unwrap());
+
+        assert!(is_resource("crate::ctx::Ctx"));
+        assert!(is_resource("&ctx::Ctx"));
+        // A type written by name alone might be any `Ctx`.
+        assert!(!is_resource("Ctx"));
+        assert!(!is_resource("other::Ctx"));
+    }
 
-    HandlerParams { params, result }
+    #[test]
+    fn default_rpc_files_below_a_lib_rpc_root() {
+        let root = fixture(
+            "lib_rpc_root",
+            &[("crates/libs/lib-rpc/src/rpcs/task_rpc.rs", TASK_RPC)],
+        );
+
+        assert_eq!(
+            methods(&scan(&root)),
+            [("task", "list_tasks", "list_tasks")]
+        );
+        assert_eq!(
+            methods(&scan(&root.join("crates/libs/lib-rpc"))),
+            [("task", "list_tasks", "list_tasks")]
+        );
+    }
+
+    #[test]
+    fn scans_handlers_types_and_bindings() {
+        let root = fixture(
+            "single_pass",
+            &[
+                (
+                    "lib-rpc/src/task_rpc.rs",
+                    r#"
+                    pub fn rpc_router() -> RpcRouter {
+                        router_builder!(create_task, list_tasks, count_tasks)
+                    }
+
+                    generate_common_rpc_fns!(
+                        Bmc: TaskBmc,
+                        Entity: Task,
+                        ForCreate: TaskForCreate,
+                        ForUpdate: TaskForUpdate,
+                        Filter: TaskFilter,
+                        Suffix: task
+                    );
+
+                    pub async fn count_tasks(ctx: Ctx, params: TaskFilter) -> Result<u32> {
+                        todo!()
+                    }
+                    "#,
+                ),
+                (
+                    "lib-core/src/model/task.rs",
+                    r#"
+                    pub struct Task { pub id: i64, pub status: TaskStatus }
+                    pub enum TaskStatus { Open, Done }
+                    pub struct TaskForCreate { pub title: String }
+                    pub struct TaskFilter { pub status: Option<TaskStatus> }
+                    pub struct Unused;
+                    "#,
+                ),
+                (
+                    "frontend/src/bindings.ts",
+                    "export interface Task {\n}\nexport type TaskStatus = \"Open\" | \"Done\";",
+                ),
+            ],
+        );
+
+        let model = scan(&root);
+        assert_eq!(
+            methods(&model),
+            [
+                ("task", "create_task", "create_task"),
+                ("task", "list_tasks", "list_tasks"),
+                ("task", "count_tasks", "count_tasks"),
+            ]
+        );
+        assert_eq!(
+            model.types.keys().collect::<Vec<_>>(),
+            [
+                "ListOptions",
+                "ParamsForCreate",
+                "ParamsList",
+                "Task",
+                "TaskFilter",
+                "TaskForCreate",
+                "TaskStatus",
+            ]
+        );
+        assert_eq!(
+            model.bindings,
+            ["Task".to_owned(), "TaskStatus".to_owned()].into()
+        );
+    }
+
+    #[test]
+    fn common_rpc_fns_list_under_their_plural() {
+        let common_rpc_fns = |entries: &str| {
+            format!(
+                r#"
+                pub fn rpc_router() -> RpcRouter {{
+                    router_builder!(list_people, list_staff, list_persons)
+                }}
+
+                generate_common_rpc_fns!(
+                    Bmc: Bmc,
+                    Entity: Item,
+                    Filter: ItemFilter,
+                    {entries}
+                );
+                "#
+            )
+        };
+        let (irregular, custom) = (
+            common_rpc_fns("Suffix: person"),
+            common_rpc_fns("Suffix: staff_member, Plural: staff"),
+        );
+        let root = fixture(
+            "common_rpc_fns_plurals",
+            &[
+                ("lib-rpc/src/person_rpc.rs", &irregular),
+                ("lib-rpc/src/staff_rpc.rs", &custom),
+            ],
+        );
+
+        let model = scan(&root);
+        // `list_persons` is what older versions of the macro generate for `person`.
+        assert_eq!(
+            methods(&model),
+            [
+                ("person", "list_people", "list_people"),
+                ("person", "list_persons", "list_persons"),
+                ("staff", "list_staff", "list_staff"),
+            ]
+        );
+        for (_, method) in model.methods() {
+            assert_eq!(
+                method.params[0].ty,
+                parser::parse_type("ParamsList<ItemFilter>").unwrap()
+            );
+            assert_eq!(
+                method.result,
+                parser::parse_type("Result<DataRpcResult<Vec<Item>>>").unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_scans_merge_in_discovery_order() {
+        let entities = [
+            "note", "task", "category", "patient", "tag", "label", "user", "team",
+        ];
+        let files: Vec<(String, String)> = entities
+            .iter()
+            .map(|entity| {
+                let content = format!(
+                    r#"
+                    pub fn rpc_router() -> RpcRouter {{
+                        router_builder!(list_{entity}s, get_{entity})
+                    }}
+
+                    pub async fn list_{entity}s(ctx: Ctx) -> Result<Vec<Item>> {{ todo!() }}
+                    pub async fn get_{entity}(ctx: Ctx, id: i64) -> Result<Item> {{ todo!() }}
+                    "#
+                );
+                (format!("lib-rpc/src/{entity}_rpc.rs"), content)
+            })
+            .collect();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.as_str()))
+            .collect();
+        let root = fixture("parallel", &files);
+
+        let sequential = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| scan(&root));
+        let parallel = scan(&root);
+        assert_eq!(parallel, sequential);
+
+        let mut sorted = entities.to_vec();
+        sorted.sort();
+        let clients: Vec<&str> = parallel.services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(clients, sorted);
+        let task = methods(&parallel)
+            .into_iter()
+            .filter(|(client, ..)| *client == "task")
+            .map(|(_, method, _)| method)
+            .collect::<Vec<_>>();
+        assert_eq!(task, ["list_tasks", "get_task"]);
+    }
 }
@@ -0,0 +1,148 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    cache::ScanCache,
+    directories::Directory,
+    process_rpc::{
+        scan_directory, RpcFileRules, DEFAULT_ENTITY_PATTERN, DEFAULT_RESOURCE_TYPES,
+        DEFAULT_RPC_FILES,
+    },
+    Result, RpcModel,
+};
+
+/// Walks a backend tree and collects its handlers and typeshare bindings.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    root: PathBuf,
+    cache_dir: Option<PathBuf>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    rpc_files: Vec<String>,
+    entity_pattern: String,
+    resource_types: Vec<String>,
+}
+
+impl Scanner {
+    /// Scanner of the tree below `root`, with the default rpc files, entity pattern and
+    /// resource types, and no cache.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Scanner {
+            root: root.as_ref().to_path_buf(),
+            cache_dir: None,
+            include: vec![],
+            exclude: vec![],
+            rpc_files: DEFAULT_RPC_FILES.iter().map(|g| g.to_string()).collect(),
+            entity_pattern: DEFAULT_ENTITY_PATTERN.to_owned(),
+            resource_types: DEFAULT_RESOURCE_TYPES
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+        }
+    }
+
+    /// Globs of the files holding handlers, matched against their path relative to the root
+    /// or their full path.
+    pub fn with_rpc_files(mut self, globs: Vec<String>) -> Self {
+        self.rpc_files = globs;
+        self
+    }
+
+    /// Regex searched for in the path of an rpc file, relative to the root, whose `entity`
+    /// group names the client its handlers go into.
+    pub fn with_entity_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.entity_pattern = pattern.into();
+        self
+    }
+
+    /// Types of the handler params the server provides, like `Ctx`, left out of the client.
+    /// A bare name matches the last segment of a type, a path like `ctx::Ctx` the types whose
+    /// path ends with it, through references.
+    pub fn with_resource_types(mut self, types: Vec<String>) -> Self {
+        self.resource_types = types;
+        self
+    }
+
+    /// Only scans the files matching one of `globs`, relative to the root.
+    pub fn with_include(mut self, globs: Vec<String>) -> Self {
+        self.include = globs;
+        self
+    }
+
+    /// Skips the files and directories matching one of the gitignore-style `globs`, on top of
+    /// those ignored by `.gitignore` files, `target` and `node_modules`.
+    pub fn with_exclude(mut self, globs: Vec<String>) -> Self {
+        self.exclude = globs;
+        self
+    }
+
+    /// Keeps the results of every scanned file in `dir`, so later scans only parse the files
+    /// whose content changed.
+    pub fn with_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Walks the root and collects the handlers of the rpc files and marked functions, the
+    /// types they use and the typeshare bindings. Fails on rpc files that don't parse, other
+    /// files that don't are skipped with a warning.
+    pub fn scan(&self) -> Result<RpcModel> {
+        let starting_dir = Directory::new(&self.root, &self.include, &self.exclude)?;
+        let rules = RpcFileRules::new(
+            &self.root,
+            &self.rpc_files,
+            &self.entity_pattern,
+            &self.resource_types,
+        )?;
+        let mut cache = ScanCache::load(self.cache_dir.as_deref(), rules.key());
+
+        let model = scan_directory(&starting_dir, &rules, &mut cache)?;
+        if let Err(e) = cache.save() {
+            eprintln!("WARNING: Couldn't save the scan cache: {e}");
+        }
+
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn services(model: &RpcModel) -> Vec<&str> {
+        model.services.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn roots_sharing_a_cache_dir_keep_their_scans_apart() {
+        let root = std::env::temp_dir().join("client_gen_scanner_shared_cache");
+        let _ = fs::remove_dir_all(&root);
+        let rpc_file = root.join("svc/src/rpcs/task_handlers.rs");
+        fs::create_dir_all(rpc_file.parent().unwrap()).unwrap();
+        fs::write(
+            &rpc_file,
+            r#"
+            pub fn rpc_router() -> RpcRouter {
+                router_builder!(list_tasks)
+            }
+
+            pub async fn list_tasks(ctx: Ctx) -> Result<DataRpcResult<Vec<Task>>> {
+                todo!()
+            }
+            "#,
+        )
+        .unwrap();
+        let scanner = |dir: &str| {
+            Scanner::new(root.join(dir))
+                .with_rpc_files(vec!["rpcs/*_handlers.rs".to_owned()])
+                .with_entity_pattern(r"(?P<entity>\w+)_handlers\.rs$")
+                .with_cache(root.join("cache"))
+        };
+
+        // The same file is an rpc file below `svc/src` only.
+        assert_eq!(services(&scanner("svc/src").scan().unwrap()), ["task"]);
+        assert!(services(&scanner("svc").scan().unwrap()).is_empty());
+        assert_eq!(services(&scanner("svc/src").scan().unwrap()), ["task"]);
+    }
+}
@@ -1,3 +1,7 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::Result;
+
 pub fn camel_to_snake(camel: &str) -> String {
     let mut snake = String::new();
 
@@ -10,3 +14,148 @@ pub fn camel_to_snake(camel: &str) -> String {
 
     snake
 }
+
+/// Words whose plural doesn't follow the suffix rules of [`pluralize`].
+const IRREGULAR_PLURALS: &[(&str, &str)] = &[
+    ("child", "children"),
+    ("person", "people"),
+    ("man", "men"),
+    ("woman", "women"),
+    ("mouse", "mice"),
+    ("foot", "feet"),
+    ("tooth", "teeth"),
+    ("data", "data"),
+    ("info", "info"),
+    ("metadata", "metadata"),
+    ("series", "series"),
+    ("species", "species"),
+    ("news", "news"),
+];
+
+/// English plural of a snake case name, pluralising its last word: `category` becomes
+/// `categories` and `task_status` becomes `task_statuses`.
+pub fn pluralize(name: &str) -> String {
+    let (head, word) = match name.rsplit_once('_') {
+        Some((head, word)) => (format!("{head}_"), word),
+        None => (String::new(), name),
+    };
+
+    if let Some((_, plural)) = IRREGULAR_PLURALS.iter().find(|(w, _)| *w == word) {
+        return format!("{head}{plural}");
+    }
+
+    let before_y = word.strip_suffix('y').and_then(|stem| stem.chars().last());
+    let plural = match before_y {
+        Some(c) if !"aeiou".contains(c) => format!("{}ies", &word[..word.len() - 1]),
+        _ if ["s", "x", "z", "ch", "sh"]
+            .iter()
+            .any(|end| word.ends_with(end)) =>
+        {
+            format!("{word}es")
+        }
+        _ => format!("{word}s"),
+    };
+
+    format!("{head}{plural}")
+}
+
+/// Whether the type written as `segments` is the one `key` names: a full path key like
+/// `rpc::Error` matches the paths ending with it, and a bare name like `Error` matches the
+/// last segment. A type written by name alone never matches a full path key, as it might be
+/// any type of that name.
+pub fn type_path_matches(key: &str, segments: &[String]) -> bool {
+    let key: Vec<&str> = key.split("::").collect();
+    match key.as_slice() {
+        [name] => segments.last().is_some_and(|last| last == name),
+        key => {
+            segments.len() >= key.len()
+                && segments[segments.len() - key.len()..]
+                    .iter()
+                    .zip(key)
+                    .all(|(segment, key)| segment == key)
+        }
+    }
+}
+
+/// Path of `to` as seen from `from_dir`, formatted as a TypeScript import specifier.
+pub fn relative_import_path(from_dir: &Path, to: &Path) -> Result<String> {
+    let from_dir = normalize(&std::path::absolute(from_dir)?);
+    let to = normalize(&std::path::absolute(to)?);
+
+    let from_components: Vec<_> = from_dir.components().collect();
+    let to_components: Vec<_> = to.components().collect();
+    let common = from_components
+        .iter()
+        .zip(to_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut parts: Vec<String> = vec![];
+    for _ in common..from_components.len() {
+        parts.push("..".to_owned());
+    }
+    for component in &to_components[common..] {
+        parts.push(component.as_os_str().to_string_lossy().into_owned());
+    }
+
+    let joined = parts.join("/");
+    if joined.is_empty() {
+        Ok(".".to_owned())
+    } else if joined.starts_with("..") {
+        Ok(joined)
+    } else {
+        Ok(format!("./{joined}"))
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camel_to_snake_splits_words() {
+        assert_eq!(camel_to_snake("TaskStatus"), "task_status");
+        assert_eq!(camel_to_snake("task"), "task");
+    }
+
+    #[test]
+    fn relative_import_path_from_client_dir() {
+        let path = |from: &str, to: &str| relative_import_path(Path::new(from), Path::new(to));
+
+        assert_eq!(
+            path("/app/src/lib/api/client", "/app/src/lib/types/bindings").unwrap(),
+            "../../types/bindings"
+        );
+        assert_eq!(
+            path("/app/src/lib", "/app/src/lib/types/./bindings").unwrap(),
+            "./types/bindings"
+        );
+        assert_eq!(path("/app/src/lib", "/app/src/lib").unwrap(), ".");
+    }
+
+    #[test]
+    fn pluralize_last_word() {
+        assert_eq!(pluralize("task"), "tasks");
+        assert_eq!(pluralize("category"), "categories");
+        assert_eq!(pluralize("day"), "days");
+        assert_eq!(pluralize("task_status"), "task_statuses");
+        assert_eq!(pluralize("box"), "boxes");
+        assert_eq!(pluralize("batch"), "batches");
+        assert_eq!(pluralize("team_person"), "team_people");
+        assert_eq!(pluralize("metadata"), "metadata");
+    }
+}
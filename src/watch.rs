@@ -0,0 +1,287 @@
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use notify::{Event, RecursiveMode, Watcher};
+
+use crate::{
+    directories::Directory, process_rpc::is_scanned_path, Error, Generator, Result, RpcModel,
+};
+
+/// How long a burst of saves has to settle before regenerating.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Generates every client, then regenerates a client whenever a Rust file or `bindings.ts`
+/// its scan would read changes. Blocks until the watcher fails.
+///
+/// Only the directories the scans walk are watched, so ignored trees like `target` don't
+/// flood the watcher. Scan errors are printed rather than returned, so a half-written file
+/// doesn't end the watch.
+pub fn watch(generators: &[Generator]) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| Error::Watch(e.to_string()))?;
+    let mut watched = BTreeSet::new();
+
+    let mut models = Vec::new();
+    let mut walks = Vec::new();
+    for generator in generators {
+        let config = generator.config();
+        let walk = walk(generator)?;
+        watch_dirs(&mut watcher, &mut watched, &walk)?;
+        walks.push(walk);
+        match generator.generate() {
+            Ok(model) => models.push(model),
+            Err(e) => {
+                eprintln!("ERROR: profile {}: {e}", config.name);
+                models.push(RpcModel::default());
+            }
+        }
+        println!(
+            "Watching {} for profile {}",
+            config.root.display(),
+            config.name
+        );
+    }
+
+    while let Some(changed) = next_changes(&rx)? {
+        let profiles = generators
+            .iter()
+            .zip(models.iter_mut())
+            .zip(walks.iter_mut());
+        for ((generator, model), walked) in profiles {
+            let config = generator.config();
+            let walk = match walk(generator) {
+                Ok(walk) => walk,
+                Err(e) => {
+                    eprintln!("ERROR: profile {}: {e}", config.name);
+                    continue;
+                }
+            };
+            watch_dirs(&mut watcher, &mut watched, &walk)?;
+            let previous = std::mem::replace(walked, walk);
+            if !affects(&changed, &previous, walked) {
+                continue;
+            }
+
+            let scanned = generator
+                .scan()
+                .and_then(|scanned| generator.write(&scanned).map(|_| scanned));
+            match scanned {
+                Ok(scanned) => {
+                    let services = changed_services(model, &scanned);
+                    let services = match services.is_empty() {
+                        true => "no handlers changed".to_owned(),
+                        false => format!("changed: {}", services.join(", ")),
+                    };
+                    println!(
+                        "Regenerated {} for profile {}, {services}",
+                        config.output_file.display(),
+                        config.name
+                    );
+                    *model = scanned;
+                }
+                Err(e) => eprintln!("ERROR: profile {}: {e}", config.name),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The files and directories below the root of `generator` its scans would walk.
+fn walk(generator: &Generator) -> Result<Directory> {
+    let config = generator.config();
+    Directory::new(&config.root, &config.include, &config.exclude)
+}
+
+/// Watches the directories of `walk` not `watched` yet, each on its own so the ones the
+/// walk skips aren't. Directories created later are picked up by the next walk, and
+/// deleted ones are forgotten so they're watched again when recreated.
+fn watch_dirs(
+    watcher: &mut impl Watcher,
+    watched: &mut BTreeSet<PathBuf>,
+    walk: &Directory,
+) -> Result<()> {
+    watched.retain(|dir| dir.is_dir());
+    for dir in walk.dirs.iter() {
+        if watched.contains(dir) {
+            continue;
+        }
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| Error::Watch(format!("{}: {e}", dir.display())))?;
+        watched.insert(dir.clone());
+    }
+
+    Ok(())
+}
+
+/// Whether the `changed` paths change what a scan reads: a file listed by the walk from
+/// before the changes, changed or deleted, or by the one from after them, created. Files
+/// the walk from after finds first count too, as those created in a new directory before
+/// it was watched never show up in `changed`.
+fn affects(changed: &BTreeSet<PathBuf>, before: &Directory, after: &Directory) -> bool {
+    let listed = |path: &PathBuf| {
+        before.files.binary_search(path).is_ok() || after.files.binary_search(path).is_ok()
+    };
+
+    changed
+        .iter()
+        .any(|path| is_scanned_path(path) && listed(path))
+        || after
+            .files
+            .iter()
+            .any(|path| is_scanned_path(path) && before.files.binary_search(path).is_err())
+}
+
+/// Blocks until something changes, then collects the changed paths until none arrive for
+/// [`DEBOUNCE`]. `None` once the watcher is gone.
+fn next_changes(rx: &Receiver<notify::Result<Event>>) -> Result<Option<BTreeSet<PathBuf>>> {
+    let mut changed = BTreeSet::new();
+
+    loop {
+        let event = match changed.is_empty() {
+            true => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            false => rx.recv_timeout(DEBOUNCE),
+        };
+        match event {
+            Ok(event) => {
+                let event = event.map_err(|e| Error::Watch(e.to_string()))?;
+                // Reads, including our own scans, aren't changes.
+                if event.kind.is_access() {
+                    continue;
+                }
+                changed.extend(event.paths);
+            }
+            Err(RecvTimeoutError::Timeout) => return Ok(Some(changed)),
+            Err(RecvTimeoutError::Disconnected) if changed.is_empty() => return Ok(None),
+            Err(RecvTimeoutError::Disconnected) => return Ok(Some(changed)),
+        }
+    }
+}
+
+/// Names of the services added, removed or changed from `old` to `new`.
+fn changed_services(old: &RpcModel, new: &RpcModel) -> Vec<String> {
+    let mut names: Vec<&String> = old
+        .services
+        .iter()
+        .chain(new.services.iter())
+        .map(|service| &service.name)
+        .collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter(|name| {
+            let old = old.services.iter().find(|s| &s.name == *name);
+            let new = new.services.iter().find(|s| &s.name == *name);
+            old != new
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use notify::event::{AccessKind, EventKind, ModifyKind};
+
+    use super::*;
+    use crate::backend::test_model::{method, model};
+
+    fn event(kind: EventKind, path: &str) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(PathBuf::from(path)))
+    }
+
+    fn paths(paths: &[&str]) -> BTreeSet<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    fn directory(files: &[&str]) -> Directory {
+        Directory {
+            files: files.iter().map(PathBuf::from).collect(),
+            dirs: vec![PathBuf::from("/svc")],
+        }
+    }
+
+    #[test]
+    fn changed_services_lists_added_removed_and_changed() {
+        let old = RpcModel {
+            services: [
+                model("task", vec![method("get_task", &[("id", "i64")], "Task")]),
+                model("project", vec![method("get_project", &[], "Project")]),
+                model("label", vec![method("get_label", &[], "Label")]),
+            ]
+            .into_iter()
+            .flat_map(|model| model.services)
+            .collect(),
+            ..Default::default()
+        };
+        let new = RpcModel {
+            services: [
+                model(
+                    "task",
+                    vec![method("get_task", &[("id", "String")], "Task")],
+                ),
+                model("project", vec![method("get_project", &[], "Project")]),
+                model("user", vec![method("get_user", &[], "User")]),
+            ]
+            .into_iter()
+            .flat_map(|model| model.services)
+            .collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(changed_services(&old, &new), ["label", "task", "user"]);
+        assert!(changed_services(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn next_changes_debounces_a_burst_and_skips_reads() {
+        let (tx, rx) = mpsc::channel();
+        let modify = EventKind::Modify(ModifyKind::Any);
+        let sender = thread::spawn(move || {
+            tx.send(event(modify, "/svc/a.rs")).unwrap();
+            thread::sleep(DEBOUNCE / 3);
+            tx.send(event(EventKind::Access(AccessKind::Any), "/svc/read.rs"))
+                .unwrap();
+            tx.send(event(modify, "/svc/b.rs")).unwrap();
+            thread::sleep(DEBOUNCE * 3);
+            tx.send(event(modify, "/svc/c.rs")).unwrap();
+        });
+
+        let first = next_changes(&rx).unwrap();
+        assert_eq!(first, Some(paths(&["/svc/a.rs", "/svc/b.rs"])));
+        sender.join().unwrap();
+        // Pending changes are still handed over once the watcher is gone.
+        assert_eq!(next_changes(&rx).unwrap(), Some(paths(&["/svc/c.rs"])));
+        assert_eq!(next_changes(&rx).unwrap(), None);
+    }
+
+    #[test]
+    fn affects_only_files_the_scan_reads() {
+        let before = directory(&["/svc/bindings.ts", "/svc/task_rpc.rs"]);
+        let after = directory(&["/svc/bindings.ts", "/svc/task_rpc.rs"]);
+
+        assert!(affects(&paths(&["/svc/task_rpc.rs"]), &before, &after));
+        assert!(affects(&paths(&["/svc/bindings.ts"]), &before, &after));
+        // Excluded by the walk, or not read by a scan.
+        assert!(!affects(&paths(&["/svc/target/gen.rs"]), &before, &after));
+        assert!(!affects(&paths(&["/svc/README.md"]), &before, &after));
+
+        let deleted = directory(&["/svc/bindings.ts"]);
+        assert!(affects(&paths(&["/svc/task_rpc.rs"]), &before, &deleted));
+        // Created in a directory that wasn't watched yet, so only the walk finds it.
+        let created = directory(&[
+            "/svc/bindings.ts",
+            "/svc/new/user_rpc.rs",
+            "/svc/task_rpc.rs",
+        ]);
+        assert!(affects(&paths(&["/svc/new"]), &before, &created));
+    }
+}